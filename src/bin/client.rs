@@ -0,0 +1,25 @@
+use junglebeast::shared::*;
+use junglebeast::client::*;
+
+fn conf() -> Conf {
+    Conf {
+        window_title: String::from("JUNGLEBEAST"),
+        window_width: 1260,
+        window_height: 768,
+        fullscreen: false,
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(conf)]
+async fn main() {
+    let args = ClientArgs::parse();
+    let mut client = match Client::create(args) {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    client.start().await;
+}
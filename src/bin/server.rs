@@ -0,0 +1,12 @@
+use junglebeast::shared::*;
+use junglebeast::server::*;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let args = ServerArgs::parse();
+    let mut server = Server::create(args);
+    if let Err(err) = server.start().await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::shared::*;
+use crate::navmesh::*;
+use crate::gamemode::same_team;
+
+// a bot is a fake client, it sends the same messages a player would
+
+const REPATH_TIME: f32 = 5.0;
+const STUCK_TIME: f32 = 2.0;
+const WAYPOINT_DISTANCE: f32 = 0.75;
+const SIGHT_DISTANCE: f32 = 50.0;
+const REACTION_TIME: f32 = 0.3;
+const MISS_CHANCE: f32 = 0.3;
+
+pub struct Bot {
+    pub client_id: ClientId,
+    path: Vec<Vec3>,
+    repath_timer: f32,
+    stuck_timer: f32,
+    last_pos: Vec3,
+    target: Option<Entity>,
+    next_shot: f32,
+}
+
+impl Bot {
+    pub fn new(client_id: ClientId) -> Self {
+        Self {
+            client_id,
+            path: Vec::new(),
+            repath_timer: 0.0,
+            stuck_timer: 0.0,
+            last_pos: Vec3::ZERO,
+            target: None,
+            next_shot: 0.0,
+        }
+    }
+
+    pub fn think(&mut self, shared: &Shared, navmesh: &NavMesh, id: Entity, tick: Tick, time: f32, dt: f32) -> ClientMessages {
+        let Ok(player) = shared.ecs.get::<&Player>(id) else { return Vec::new() };
+        let Some(pos) = shared.body_position(id) else { return Vec::new() };
+
+        if player.dead() {
+            self.path.clear();
+            self.target = None;
+            return if player.can_respawn(time) { vec![ClientMessage::Respawn] } else { Vec::new() };
+        }
+
+        let mut msgs = Vec::new();
+
+        self.update_path(shared, navmesh, pos, dt);
+
+        // look at the enemy if there is one, otherwise where we are going
+        let target = self.find_target(shared, id, pos);
+        let enemy = target.map(|(enemy, _)| enemy);
+        if enemy != self.target {
+            self.target = enemy;
+            self.next_shot = self.next_shot.max(time + REACTION_TIME);
+        }
+
+        let look_at = match target {
+            Some((_, point)) => Some(point),
+            None => self.path.first().copied(),
+        };
+        let yaw = match look_at {
+            Some(point) => (point.z - pos.z).atan2(point.x - pos.x),
+            None => player.cmd.yaw,
+        };
+
+        let mut cmd = UserCmd { tick, yaw, weapon: player.cmd.weapon, ..UserCmd::default() };
+        if let Some(waypoint) = self.path.first() {
+            // split the direction to the waypoint into forward and sideways
+            let dir = (*waypoint - pos).xz().normalize_or_zero();
+            let forward = vec2(yaw.cos(), yaw.sin());
+            let right = vec2(-forward.y, forward.x);
+
+            cmd.forward = dir.dot(forward);
+            cmd.side = dir.dot(right);
+
+            // let go in between, every jump needs a new press
+            if waypoint.y - pos.y > MAX_STEP && !player.cmd.pressed(BUTTON_JUMP) {
+                cmd.press(BUTTON_JUMP);
+            }
+        }
+
+        if let Some((enemy, point)) = target {
+            let weapon = Weapon::ALL.into_iter().rev()
+                .find(|weapon| player.has_weapon(*weapon) && player.ammo(*weapon) > 0);
+
+            if let Some(weapon) = weapon {
+                cmd.weapon = weapon;
+
+                if time >= self.next_shot {
+                    self.next_shot = time + fire_delay(weapon);
+                    cmd.press(BUTTON_FIRE);
+                    if gen_range(0.0, 1.0) >= MISS_CHANCE {
+                        msgs.push(ClientMessage::Shot { target: enemy, point, weapon });
+                    }
+                }
+            }
+        }
+
+        msgs.push(ClientMessage::UserCmds(vec![cmd]));
+        msgs
+    }
+
+    fn update_path(&mut self, shared: &Shared, navmesh: &NavMesh, pos: Vec3, dt: f32) {
+        while self.path.first().is_some_and(|waypoint| waypoint.xz().distance(pos.xz()) < WAYPOINT_DISTANCE) {
+            self.path.remove(0);
+        }
+
+        if pos.distance(self.last_pos) < shared.cvars.sv_move_speed * dt * 0.1 {
+            self.stuck_timer += dt;
+        } else {
+            self.stuck_timer = 0.0;
+        }
+        self.last_pos = pos;
+        self.repath_timer -= dt;
+
+        if self.path.is_empty() || self.repath_timer <= 0.0 || self.stuck_timer > STUCK_TIME {
+            let goal = pick_goal(shared).or_else(|| navmesh.random_node());
+            self.path = goal.and_then(|goal| navmesh.find_path(pos, goal)).unwrap_or_default();
+            self.repath_timer = REPATH_TIME;
+            self.stuck_timer = 0.0;
+        }
+    }
+
+    // the closest enemy that can be seen, and the point the ray hits
+    fn find_target(&self, shared: &Shared, id: Entity, pos: Vec3) -> Option<(Entity, Vec3)> {
+        let mut enemies: Vec<(Entity, Vec3)> = shared.ecs.query::<&Player>().iter()
+            .filter(|(other, player)| *other != id && !player.dead() && !same_team(shared, id, *other))
+            .filter_map(|(other, _)| shared.body_position(other).map(|pos| (other, pos)))
+            .filter(|(_, other_pos)| other_pos.distance(pos) < SIGHT_DISTANCE)
+            .collect();
+        enemies.sort_by(|a, b| a.1.distance(pos).total_cmp(&b.1.distance(pos)));
+
+        enemies.into_iter().find_map(|(enemy, enemy_pos)| {
+            let dir = (enemy_pos - pos).normalize_or_zero();
+            match shared.ray_intersection(pos, dir, &[id]) {
+                Some((point, Some(hit))) if hit == enemy => Some((enemy, point)),
+                _ => None,
+            }
+        })
+    }
+}
+
+// spawn points and available items
+fn pick_goal(shared: &Shared) -> Option<Vec3> {
+    let mut goals: Vec<Vec3> = shared.ecs.query::<(&PointObject, &Properties)>().iter()
+        .filter(|(_, (_, props))| props.spawn == Some(true))
+        .map(|(_, (pos, _))| pos.0)
+        .collect();
+    goals.extend(shared.ecs.query::<&Item>().iter()
+        .filter(|(_, item)| item.available())
+        .map(|(_, item)| item.pos));
+
+    goals.choose().copied()
+}
+
+fn fire_delay(weapon: Weapon) -> f32 {
+    match weapon {
+        Weapon::Gun => 0.5,
+        Weapon::Rail => 1.5,
+    }
+}
@@ -1,35 +1,197 @@
-mod shared;
-mod physics;
-mod network;
-mod utils;
-mod components;
 use crate::shared::*;
+use crate::interpolation::*;
+use crate::clock::*;
+use crate::demo::*;
+use crate::server::*;
 
-use renet::{RenetClient, DefaultChannel};
-use renet_netcode::NetcodeClientTransport;
+use renet::RenetClient;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver};
 
-struct Client {
-    shared: Shared,
+pub struct Client {
+    pub shared: Shared,
     client: RenetClient,
-    transport: NetcodeClientTransport,
-    player: Entity,
+    transport: Option<ClientTransport>, // None when playing a demo
+    pub player: Entity,
+    transfers: TransferReceiver,
+    last_snapshot: Option<Instant>,
+    last_snapshot_tick: Option<Tick>,
+    pub server_tick: Tick, // of the batch being handled
+    clock: ServerClock,
+    start: Instant,
+    show_net_stats: bool,
+    snapshots: HashMap<RigidBodyHandle, SnapshotBuffer>,
+    despawned: HashMap<Entity, Tick>, // until no older snapshot can come in
+    models: HashMap<ModelId, Vec<(MeshWrapper, Transform)>>, // until they are uploaded
+    model_meshes: HashMap<ModelId, Vec<(Mesh, Transform)>>,
+    kill_feed: VecDeque<(Instant, DamageEvent)>,
+    match_state: Option<MatchState>,
+    weapon: Weapon,
+    cmd: UserCmd, // the input of this frame
+    pressed: u8, // buttons down in any frame since the last command was sent
+    sent_cmds: VecDeque<UserCmd>,
+    cmd_accumulator: f32,
+    eye_height: f32, // follows the one of the player smoothly, crouching doesn't jerk the camera
+    pub loaded: bool, // has the initial world arrived?
+    auto_join: bool,
+    view: View,
+    chat_input: Option<ChatInput>,
+    chat_log: VecDeque<(Instant, String)>,
+    rcon_password: String,
+    recorder: Option<DemoRecorder>,
+    demo: Option<DemoPlayer>,
+    server_error: Option<Receiver<String>>, // from the listen server, when it fails to start
 }
 
+// the message being typed
+struct ChatInput {
+    text: String,
+    team_only: bool,
+}
+
+// how a spectator looks at the world
+enum View {
+    FreeFly { pos: Vec3 },
+    Follow(Entity),
+}
+
+const FLY_SPEED: f32 = 15.0;
+
+const KILL_FEED_TIME: Duration = Duration::from_secs(5);
+const KILL_FEED_LEN: usize = 5;
+
+const CHAT_TIME: Duration = Duration::from_secs(10);
+const CHAT_LEN: usize = 8;
+
+// seconds
+const DEMO_SEEK: f32 = 5.0;
+const MAX_DEMO_SPEED: f32 = 8.0;
+
 impl Client {
-    fn create(addr: String) -> Self {
-        let (client, transport) = create_client(addr);
+    pub fn create(args: ClientArgs) -> Result<Self, String> {
+        match (args.command, args.connect) {
+            (Some(ClientCommand::Play(args)), _) => Self::play(args),
+            (Some(ClientCommand::Host(args)), _) => Self::host(args),
+            (None, Some(args)) => Ok(Self::connect(args)),
+            (None, None) => unreachable!("clap requires the address"),
+        }
+    }
+
+    // runs a listen server on its own thread, connected through the loopback
+    fn host(args: ServerArgs) -> Result<Self, String> {
+        let addr = args.addr();
+        let (local, remote) = loopback(addr);
+
+        // bound here so a taken port is reported before the window opens
+        let error = |err: std::io::Error| format!("failed to bind {}: {}", addr, err);
+        let udp = UdpSocket::bind(addr).map_err(error)?;
+        udp.set_nonblocking(true).map_err(error)?;
+
+        let (error_sender, error_receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let mut server = Server::host(args, remote, udp);
+                if let Err(err) = server.start().await {
+                    let _ = error_sender.send(err);
+                }
+            });
+        });
+
+        let mut this = Self::with_socket(addr, Box::new(local));
+        this.auto_join = true;
+        this.server_error = Some(error_receiver);
+        Ok(this)
+    }
+
+    // no window needed until start, used by tests with a loopback socket
+    pub fn with_socket(addr: SocketAddr, socket: Box<dyn Socket>) -> Self {
+        let (client, transport) = create_client_with_socket(addr, socket);
+        Self::new(client, Some(transport))
+    }
+
+    fn connect(args: ConnectArgs) -> Self {
+        let (client, transport) = create_client(args.common.addr, &args.common.conditions);
+
+        let mut this = Self::new(client, Some(transport));
+        if let Err(err) = this.shared.cvars.apply_args(&args.common.cvars) {
+            ClientArgs::command().error(ErrorKind::ValueValidation, err).exit();
+        }
+        this.auto_join = !args.spectate;
+        this.rcon_password = args.rcon_password.unwrap_or_default();
+        this.recorder = args.record.as_deref().map(DemoRecorder::create).transpose()
+            .unwrap_or_else(|err| ClientArgs::command().error(ErrorKind::Io, err).exit());
+        this
+    }
+
+    // the client never connects, the messages come from the demo
+    fn play(args: PlayArgs) -> Result<Self, String> {
+        let mut demo = DemoPlayer::load(&args.demo)?;
+        demo.speed = args.speed;
+
+        let mut this = Self::new(RenetClient::new(connection_config()), None);
+        this.demo = Some(demo);
+        Ok(this)
+    }
+
+    fn new(client: RenetClient, transport: Option<ClientTransport>) -> Self {
+        let mut shared = Shared::new();
+        shared.cvars.load_config(CONFIG_PATH);
 
         Self {
-            shared: Shared::new(),
+            shared,
             client,
             transport,
             player: Entity::DANGLING,
+            transfers: TransferReceiver::new(),
+            last_snapshot: None,
+            last_snapshot_tick: None,
+            server_tick: 0,
+            clock: ServerClock::default(),
+            start: Instant::now(),
+            show_net_stats: true,
+            snapshots: HashMap::new(),
+            despawned: HashMap::new(),
+            models: load_models(),
+            model_meshes: HashMap::new(),
+            kill_feed: VecDeque::new(),
+            match_state: None,
+            weapon: Weapon::Gun,
+            cmd: UserCmd::default(),
+            pressed: 0,
+            sent_cmds: VecDeque::new(),
+            cmd_accumulator: 0.0,
+            eye_height: EYE_HEIGHT,
+            loaded: false,
+            auto_join: false,
+            view: View::FreeFly { pos: vec3(0.0, 15.0, 0.0) },
+            chat_input: None,
+            chat_log: VecDeque::new(),
+            rcon_password: String::new(),
+            recorder: None,
+            demo: None,
+            server_error: None,
         }
     }
 
-    async fn start(&mut self) {
+    // forgets the world, before a new map or when a demo starts over
+    fn reset(&mut self) {
+        let cvars = self.shared.cvars.clone();
+        self.shared = Shared::new();
+        self.shared.cvars = cvars;
+        self.player = Entity::DANGLING;
+        self.loaded = false;
+        self.snapshots.clear();
+        self.despawned.clear();
+        self.kill_feed.clear();
+        self.match_state = None;
+        self.view = View::FreeFly { pos: vec3(0.0, 15.0, 0.0) };
+    }
+
+    pub async fn start(&mut self) {
         self.load_textures().await;
 
         let mut x = 0.0;
@@ -42,8 +204,6 @@ impl Client {
 
         let mut last_mouse_position: Vec2 = mouse_position().into();
 
-        let look_speed = 0.1;
-
         let mut grabbed = true;
         set_cursor_grab(grabbed);
         show_mouse(!grabbed);
@@ -51,10 +211,21 @@ impl Client {
         let mut dt_accumulator = 0.0;
 
         loop {
+            // the listen server is gone, nothing would ever arrive
+            if let Some(err) = self.server_error.as_ref().and_then(|errors| errors.try_recv().ok()) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+
             let delta = get_frame_time();
             dt_accumulator += delta;
 
-            if (grabbed && is_key_pressed(KeyCode::Escape))
+            let mut messages = ClientMessages::new();
+
+            // while typing, the keys don't do anything else
+            let typing = self.handle_chat_input(&mut messages);
+
+            if !typing && (grabbed && is_key_pressed(KeyCode::Escape))
                 || (!grabbed && is_mouse_button_pressed(MouseButton::Left)) {
 
                 grabbed = !grabbed;
@@ -62,12 +233,17 @@ impl Client {
                 show_mouse(!grabbed);
             }
 
+            if is_key_pressed(KeyCode::F3) {
+                self.show_net_stats = !self.show_net_stats;
+            }
+
             let mouse_position: Vec2 = mouse_position().into();
             let mouse_delta = mouse_position - last_mouse_position;
 
             if grabbed {
                 last_mouse_position = mouse_position;
 
+                let look_speed = self.shared.cvars.cl_look_speed;
                 yaw += mouse_delta.x * delta * look_speed;
                 pitch += mouse_delta.y * delta * -look_speed;
 
@@ -82,55 +258,80 @@ impl Client {
             )
             .normalize();
 
-            // TODO
-            // if let Ok(mut obj) = self.shared.ecs.get::<&mut PhysicsObject>(self.player) {
-            //     obj.set_yaw(yaw);
-            // }
-
             x += if switch { 0.04 } else { -0.04 };
             if x >= bounds || x <= -bounds {
                 switch = !switch;
             }
 
-            let mut messages = ClientMessages::new();
+            if self.auto_join && self.client.is_connected() {
+                self.auto_join = false;
+                messages.push(ClientMessage::Join);
+            }
 
             if let Ok(player) = self.shared.ecs.query_one_mut::<&mut Player>(self.player) {
-                player.moves.reset();
+                if player.dead() {
+                    if is_key_pressed(KeyCode::Space) {
+                        messages.push(ClientMessage::Respawn);
+                    }
+                } else {
+                    let mut cmd = UserCmd { yaw, pitch, ..UserCmd::default() };
 
-                // TODO
-                // messages.push(ClientMessage::SetYaw(obj.yaw()));
+                    if !typing {
+                        let axis = |positive, negative| is_key_down(positive) as i32 as f32 - is_key_down(negative) as i32 as f32;
+                        cmd.forward = axis(KeyCode::W, KeyCode::S);
+                        cmd.side = axis(KeyCode::D, KeyCode::A);
 
-                if is_key_down(KeyCode::W) { player.moves.forward = true; }
-                if is_key_down(KeyCode::S) { player.moves.back = true; }
-                if is_key_down(KeyCode::A) { player.moves.left = true; }
-                if is_key_down(KeyCode::D) { player.moves.right = true; }
+                        for (key, button) in [(KeyCode::Space, BUTTON_JUMP), (KeyCode::LeftControl, BUTTON_CROUCH), (KeyCode::E, BUTTON_USE)] {
+                            if is_key_down(key) || is_key_pressed(key) {
+                                cmd.press(button);
+                            }
+                        }
+                        if grabbed && is_mouse_button_down(MouseButton::Left) {
+                            cmd.press(BUTTON_FIRE);
+                        }
 
-                if is_key_pressed(KeyCode::Space) {
-                    player.moves.set_jump();
-                }
+                        for (key, weapon) in [KeyCode::Key1, KeyCode::Key2].into_iter().zip(Weapon::ALL) {
+                            if is_key_pressed(key) && player.has_weapon(weapon) {
+                                self.weapon = weapon;
+                            }
+                        }
+                    }
+                    cmd.weapon = self.weapon;
 
-                messages.push(ClientMessage::SetMoveState(player.moves.clone()));
+                    // moved right away, the server gets it with the next command
+                    player.set_cmd(cmd.clone());
+                    self.pressed |= cmd.buttons;
+                    self.cmd = cmd;
+                    self.send_cmds(delta, &mut messages);
+                }
             }
 
-            if self.shared.ecs.entity(self.player).is_ok() {
-                while dt_accumulator >= PHYSICS_STEP {
-                    self.shared.handle_physics(PHYSICS_STEP).await;
-                    dt_accumulator -= PHYSICS_STEP;
+            if self.loaded {
+                self.upload_meshes();
+                self.interpolate_remote_bodies();
+
+                let step = self.shared.cvars.sv_physics_step;
+                while dt_accumulator >= step {
+                    self.shared.handle_physics(step).await;
+                    dt_accumulator -= step;
                 }
 
-                let (pos, up) = {
-                    let handle = self.shared.ecs.get::<&RigidBodyHandle>(self.player).unwrap();
-                    let rig = self.shared.physics.get_rig(*handle);
-                    (conv_vec_2(*rig.translation()), front.cross(world_up).cross(front).normalize())
+                let local_body = self.local_body();
+                let (pos, front) = match local_body {
+                    Some(handle) => (self.eye_position(handle, delta), front),
+                    None => self.spectate(grabbed && !typing, front, delta, &mut messages),
                 };
+                let up = front.cross(world_up).cross(front).normalize();
 
-                // if is_mouse_button_pressed(MouseButton::Left) {
-                //     if let Some((_, id)) = self.shared.ray_intersection(pos, front, &[self.player]) {
-                //         if self.shared.ecs.get::<&Player>(id).is_ok() {
-                //             messages.push(ClientMessage::Shot(id));
-                //         }
-                //     }
-                // }
+                let has_ammo = self.shared.ecs.get::<&Player>(self.player)
+                    .is_ok_and(|player| player.ammo(self.weapon) > 0);
+
+                if local_body.is_some() && grabbed && !typing && has_ammo && is_mouse_button_pressed(MouseButton::Left)
+                    && let Some((point, Some(id))) = self.shared.ray_intersection(pos, front, &[self.player])
+                    && self.shared.ecs.get::<&Player>(id).is_ok() {
+
+                    messages.push(ClientMessage::Shot { target: id, point, weapon: self.weapon });
+                }
 
                 // if is_mouse_button_pressed(MouseButton::Left) {
                 //     self.ecs.spawn((physobj(
@@ -142,22 +343,211 @@ impl Client {
                     position: pos,
                     up,
                     target: pos + front,
-                    fovy: 2.05,
+                    fovy: self.shared.cvars.cl_fov,
                     ..Default::default()
                 });
 
-                self.render().await;
+                self.render(pos).await;
 
+            } else if let Some(progress) = self.transfers.progress() {
+                self.draw_loading_bar(progress);
             } else {
                 draw_text("CONNECTING...", 10.0, 30.0, 30.0, WHITE);
             }
 
-            self.handle_network(Duration::from_secs_f32(delta), messages).await;
+            if self.demo.is_some() {
+                self.handle_demo(delta, typing).await;
+            } else {
+                self.handle_network(Duration::from_secs_f32(delta), messages).await;
+            }
 
             next_frame().await
         }
     }
 
+    // T opens the chat, Y the team chat. returns true while typing
+    fn handle_chat_input(&mut self, messages: &mut ClientMessages) -> bool {
+        let Some(input) = &mut self.chat_input else {
+            let team_only = is_key_pressed(KeyCode::Y);
+            if is_key_pressed(KeyCode::T) || team_only {
+                // drop the key that opened it
+                while get_char_pressed().is_some() {}
+                self.chat_input = Some(ChatInput { text: String::new(), team_only });
+                return true;
+            }
+            return false;
+        };
+
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                input.text.push(c);
+            }
+        }
+
+        if is_key_pressed(KeyCode::Backspace) {
+            input.text.pop();
+        }
+
+        if is_key_pressed(KeyCode::Enter) {
+            let text = std::mem::take(&mut input.text);
+            let team_only = input.team_only;
+            self.chat_input = None;
+
+            if let Some(command) = text.strip_prefix("/rcon ") {
+                messages.push(ClientMessage::Rcon {
+                    password: self.rcon_password.clone(),
+                    command: command.to_string(),
+                });
+            } else if let Some(args) = text.strip_prefix("/set ") {
+                let output = self.set_cvar(args);
+                self.print(output);
+            } else if !text.trim().is_empty() {
+                messages.push(ClientMessage::Chat { text, team_only });
+            }
+        } else if is_key_pressed(KeyCode::Escape) {
+            self.chat_input = None;
+        }
+
+        true
+    }
+
+    // the camera of a spectator, free flying or looking through the eyes of a player
+    fn spectate(&mut self, input: bool, front: Vec3, delta: f32, messages: &mut ClientMessages) -> (Vec3, Vec3) {
+        if input && is_key_pressed(KeyCode::J) {
+            messages.push(ClientMessage::Join);
+        }
+
+        if input && is_mouse_button_pressed(MouseButton::Left) {
+            self.follow_next();
+        }
+
+        if let View::Follow(id) = self.view {
+            let pos = self.shared.eye_position(id);
+            let angles = self.shared.ecs.get::<&Player>(id).map(|player| (player.cmd.yaw, player.cmd.pitch));
+
+            match (pos, angles) {
+                (Some(pos), Ok((yaw, pitch))) if !(input && is_mouse_button_pressed(MouseButton::Right)) => {
+                    return (pos, vec3(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos()));
+                }
+                (pos, _) => {
+                    self.view = View::FreeFly { pos: pos.unwrap_or(vec3(0.0, 15.0, 0.0)) };
+                }
+            }
+        }
+
+        let View::FreeFly { pos } = &mut self.view else { unreachable!() };
+        let right = front.cross(vec3(0.0, 1.0, 0.0)).normalize();
+
+        let mut dir = Vec3::ZERO;
+        if input {
+            if is_key_down(KeyCode::W) { dir += front; }
+            if is_key_down(KeyCode::S) { dir -= front; }
+            if is_key_down(KeyCode::D) { dir += right; }
+            if is_key_down(KeyCode::A) { dir -= right; }
+            if is_key_down(KeyCode::Space) { dir.y += 1.0; }
+            if is_key_down(KeyCode::LeftShift) { dir.y -= 1.0; }
+        }
+        *pos += dir.normalize_or_zero() * FLY_SPEED * delta;
+
+        (*pos, front)
+    }
+
+    // cycles through the players in id order
+    fn follow_next(&mut self) {
+        let mut players: Vec<Entity> = self.shared.ecs.query::<(&Player, &RigidBodyHandle)>().iter()
+            .map(|(id, _)| id)
+            .collect();
+        players.sort_by_key(|id| id.to_bits());
+
+        let next = match self.view {
+            View::Follow(current) => players.iter()
+                .find(|id| id.to_bits() > current.to_bits())
+                .or(players.first()),
+            View::FreeFly { .. } => players.first(),
+        };
+
+        if let Some(id) = next {
+            self.view = View::Follow(*id);
+        }
+    }
+
+    // one command per server tick, with the last few again in case packets get lost
+    fn send_cmds(&mut self, delta: f32, messages: &mut ClientMessages) {
+        let interval = 1.0 / self.shared.cvars.sv_tick_rate as f32;
+        // after a long frame, more than the redundant ones would be gone anyway
+        self.cmd_accumulator = (self.cmd_accumulator + delta).min(interval * REDUNDANT_CMDS as f32);
+
+        let mut sent = false;
+        while self.cmd_accumulator >= interval {
+            self.cmd_accumulator -= interval;
+
+            let mut cmd = self.cmd.clone();
+            cmd.tick = self.next_cmd_tick();
+            cmd.buttons = std::mem::take(&mut self.pressed) | self.cmd.buttons;
+
+            self.sent_cmds.push_back(cmd);
+            if self.sent_cmds.len() > REDUNDANT_CMDS {
+                self.sent_cmds.pop_front();
+            }
+            sent = true;
+        }
+
+        if sent {
+            messages.push(ClientMessage::UserCmds(self.sent_cmds.iter().cloned().collect()));
+        }
+    }
+
+    // the tick the server will be at when the command arrives,
+    // until the clock is synced the one after the last snapshot
+    fn next_cmd_tick(&self) -> Tick {
+        let tick = match self.clock.prediction_time(self.local_time()) {
+            Some(time) => (time * self.shared.cvars.sv_tick_rate as f64).round() as Tick,
+            None => self.server_tick + 1,
+        };
+
+        match self.sent_cmds.back() {
+            Some(last) => tick.max(last.tick + 1),
+            None => tick,
+        }
+    }
+
+    // the camera of the local player, from the feet so crouching only moves it down
+    fn eye_position(&mut self, handle: RigidBodyHandle, delta: f32) -> Vec3 {
+        let pos = conv_vec_2(*self.shared.physics.get_rig(handle).translation());
+        let Ok(player) = self.shared.ecs.get::<&Player>(self.player) else { return pos };
+
+        self.eye_height += (player.eye_height() - self.eye_height) * (delta * 10.0).min(1.0);
+        pos + vec3(0.0, self.eye_height - player.size().y / 2.0, 0.0)
+    }
+
+    fn local_body(&self) -> Option<RigidBodyHandle> {
+        self.shared.ecs.get::<&RigidBodyHandle>(self.player).ok().map(|handle| *handle)
+    }
+
+    // seconds, a demo has its own time so the clock follows pausing and seeking
+    fn local_time(&self) -> f64 {
+        match &self.demo {
+            Some(demo) => demo.time() as f64,
+            None => self.start.elapsed().as_secs_f64(),
+        }
+    }
+
+    fn tick_time(&self, tick: Tick) -> f64 {
+        tick as f64 / self.shared.cvars.sv_tick_rate as f64
+    }
+
+    fn interpolate_remote_bodies(&mut self) {
+        let Some(time) = self.clock.interpolation_time(self.local_time()) else { return };
+
+        for (handle, snapshots) in self.snapshots.iter_mut() {
+            if let Some(pos) = snapshots.sample(time)
+                && let Some(body) = self.shared.physics.state.rigid_body_set.get_mut(*handle) {
+
+                body.set_next_kinematic_position(pos);
+            }
+        }
+    }
+
     async fn load_textures(&mut self) {
         // TODO
         // see if there is a less complicated way that does not use unsafe,
@@ -178,8 +568,19 @@ impl Client {
     }
 
     async fn handle_msg(&mut self, msg: ServerMessage) {
+        // snapshots of a world that isn't loaded (yet, or anymore after a map change)
+        if !self.loaded && matches!(msg, ServerMessage::Ecs(_) | ServerMessage::PhysicsDiff(_)) {
+            return;
+        }
+
         match msg {
-            ServerMessage::Ecs(columns) => {
+            ServerMessage::Ecs(mut columns) => {
+                // a snapshot from before the despawn would bring the entity back,
+                // and older snapshots than this one are dropped from now on
+                let tick = self.server_tick;
+                columns.retain(|id| self.despawned.get(id).is_none_or(|despawned| tick >= *despawned));
+                self.despawned.retain(|_, despawned| *despawned > tick);
+
                 for id in columns.ids() {
                     if !self.shared.ecs.contains(*id) {
                         self.shared.ecs.spawn_at(*id, ());
@@ -213,13 +614,46 @@ impl Client {
                     self.shared.ecs.insert(id, (handle,)).unwrap();
                 }
 
+                // turned into meshes by upload_meshes, which needs the window
                 for (id, wrapper) in columns.MeshWrapper {
-                    self.shared.ecs.insert(id, (wrapper.to_mesh(),)).unwrap();
+                    self.shared.ecs.insert(id, (wrapper,)).unwrap();
+                }
+
+                for (id, water) in columns.Water {
+                    self.shared.ecs.insert(id, (water,)).unwrap();
+                }
+
+                for (id, transform) in columns.Transform {
+                    self.shared.ecs.insert(id, (transform,)).unwrap();
+                }
+
+                // drawn where the body is, sync_transforms keeps them there
+                for (id, model) in columns.ModelId {
+                    self.shared.ecs.insert(id, (model,)).unwrap();
+                    if self.shared.ecs.get::<&Transform>(id).is_err() {
+                        self.shared.ecs.insert(id, (Transform::new(Vec3::ZERO),)).unwrap();
+                    }
                 }
 
                 for (id, obj) in columns.Player {
                     self.shared.ecs.insert(id, (obj,)).unwrap();
                 }
+
+                for (id, score) in columns.Score {
+                    self.shared.ecs.insert(id, (score,)).unwrap();
+                }
+
+                for (id, team) in columns.Team {
+                    self.shared.ecs.insert(id, (team,)).unwrap();
+                }
+
+                for (id, flag) in columns.Flag {
+                    self.shared.ecs.insert(id, (flag,)).unwrap();
+                }
+
+                for (id, item) in columns.Item {
+                    self.shared.ecs.insert(id, (item,)).unwrap();
+                }
             }
             ServerMessage::AssignId(id) => {
                 self.player = id;
@@ -227,67 +661,145 @@ impl Client {
             ServerMessage::PhysicsState(rigid_body_set, collider_set) => {
                 self.shared.physics.state.rigid_body_set = rigid_body_set;
                 self.shared.physics.state.collider_set = collider_set;
+                self.snapshots.clear();
+                self.loaded = true;
             }
             ServerMessage::PhysicsDiff((rigid_body_updates, collider_updates)) => {
+                // the diff has everything, so anything new or gone means the sets changed
+                let state = &self.shared.physics.state;
+                let same_bodies = state.rigid_body_set.len() == rigid_body_updates.len()
+                    && rigid_body_updates.iter().all(|(handle, _)| state.rigid_body_set.contains(*handle));
+                let same_colliders = state.collider_set.len() == collider_updates.len()
+                    && collider_updates.iter().all(|(handle, _)| state.collider_set.contains(*handle));
+
+                if !(same_bodies && same_colliders) {
+                    if !self.shared.physics.state.mirror((rigid_body_updates.clone(), collider_updates.clone())) {
+                        eprintln!("physics diff has handles that can't be made");
+                    }
+                    let bodies = &self.shared.physics.state.rigid_body_set;
+                    self.snapshots.retain(|handle, _| bodies.contains(*handle));
+                }
+
+                let local_body = self.local_body();
+                let time = self.tick_time(self.server_tick);
+
                 for (handle, rig) in rigid_body_updates {
                     if self.shared.physics.state.rigid_body_set.contains(handle) {
-                        self.shared.physics.get_rig_mut(handle).copy_from(&rig);
-                    } else {
-                        let len = self.shared.physics.state.rigid_body_set.len();
-                        let index = handle.0.into_raw_parts().0 as usize;
-                        for new_index in len..=index {
-                            self.shared.physics.state.rigid_body_set.insert(
-                                if new_index == index {
-                                    rig.clone()
-                                } else {
-                                    RigidBodyBuilder::dynamic().build()
-                                }
-                            );
+                        if Some(handle) == local_body {
+                            self.shared.physics.get_rig_mut(handle).copy_from(&rig);
+                        } else {
+                            // remote bodies are moved by interpolate_remote_bodies instead
+                            let body = self.shared.physics.get_rig_mut(handle);
+                            if !body.is_kinematic() {
+                                body.set_body_type(RigidBodyType::KinematicPositionBased, true);
+                            }
+                            self.snapshots.entry(handle).or_default().push(time, *rig.position());
                         }
                     }
                 }
 
-                // the same shit again:
-
                 for (handle, col) in collider_updates {
                     if self.shared.physics.state.collider_set.contains(handle) {
                         self.shared.physics.get_col_mut(handle).copy_from(&col);
-                    } else {
-                        let len = self.shared.physics.state.collider_set.len();
-                        let index = handle.0.into_raw_parts().0 as usize;
-                        for new_index in len..=index {
-                            self.shared.physics.state.collider_set.insert(
-                                if new_index == index {
-                                    col.clone()
-                                } else {
-                                    ColliderBuilder::ball(0.0).build()
-                                }
-                            );
-                        }
+                    }
+                }
+            }
+            ServerMessage::Kill(event) => {
+                self.kill_feed.push_back((Instant::now(), event));
+                if self.kill_feed.len() > KILL_FEED_LEN {
+                    self.kill_feed.pop_front();
+                }
+            }
+            ServerMessage::Despawn(id) => {
+                let _ = self.shared.ecs.despawn(id);
+                self.despawned.insert(id, self.server_tick);
+            }
+            ServerMessage::Match(state) => {
+                self.match_state = Some(state);
+            }
+            ServerMessage::Chat { from, text } => {
+                let line = match from {
+                    Some(id) => format!("{}: {}", self.player_name(id), text),
+                    None => text,
+                };
+                self.print(line);
+            }
+            ServerMessage::Cvars(cvars) => {
+                self.shared.cvars.replicate(&cvars);
+            }
+            ServerMessage::RconOutput(output) => {
+                for line in output.lines() {
+                    self.print(line.to_string());
+                }
+            }
+            ServerMessage::Tick(tick) => {
+                self.server_tick = tick;
+
+                // a demo has no latency, the ticks are the clock
+                if self.demo.is_some() {
+                    self.clock.sync(self.local_time(), self.tick_time(tick));
+                }
+            }
+            ServerMessage::Pong(sent) => {
+                self.clock.pong(sent, self.local_time(), self.tick_time(self.server_tick));
+            }
+            ServerMessage::ChangeMap(map) => {
+                println!("changing map to {}", map);
+                self.reset();
+            }
+            ServerMessage::Chunk(chunk) => {
+                if let Some(data) = self.transfers.receive(chunk) {
+                    match deserialize::<ServerMessages>(&data.into()) {
+                        Ok(msgs) =>
+                            for msg in msgs {
+                                Box::pin(self.handle_msg(msg)).await;
+                            }
+                        Err(err) =>
+                            eprintln!("{}", err)
                     }
                 }
             }
         }
     }
 
-    async fn handle_network(&mut self, duration: Duration, send_msgs: ClientMessages) {
+    pub async fn handle_network(&mut self, duration: Duration, mut send_msgs: ClientMessages) {
+        let now = self.local_time();
+        let Some(transport) = &mut self.transport else { return };
         self.client.update(duration);
-        self.transport.update(duration, &mut self.client).unwrap();
+        transport.update(duration, &mut self.client).unwrap();
 
         let mut msgs = Vec::new();
 
         if self.client.is_connected() {
-            self.client.send_message(DefaultChannel::ReliableOrdered,
-                serialize(send_msgs).unwrap()
-            );
+            send_msgs.extend(self.clock.ping(now));
 
-            for channel in NET_CHANNELS {
+            for (channel, batch) in split_by_channel(send_msgs) {
+                self.client.send_message(channel, serialize(batch).unwrap());
+            }
+
+            for channel in Channel::ALL {
                 while let Some(ref data) = self.client.receive_message(channel) {
+                    if channel == Channel::Snapshot {
+                        self.last_snapshot = Some(Instant::now());
+                    }
+
                     match deserialize::<ServerMessages>(data) {
-                        Ok(new_msgs) =>
-                            for msg in new_msgs {
-                                msgs.push(msg);
+                        Ok(new_msgs) => {
+                            // unreliable, so an older snapshot can arrive after a newer one
+                            if channel == Channel::Snapshot
+                                && let Some(ServerMessage::Tick(tick)) = new_msgs.first() {
+
+                                if self.last_snapshot_tick.is_some_and(|last| *tick < last) {
+                                    continue;
+                                }
+                                self.last_snapshot_tick = Some(*tick);
+                            }
+
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.record(&new_msgs);
                             }
+                            msgs.extend(new_msgs);
+                        }
                         Err(err) => 
                             eprintln!("{}", err)
                     }
@@ -295,14 +807,96 @@ impl Client {
             }
         }
 
-        self.transport.send_packets(&mut self.client).unwrap();
+        transport.send_packets(&mut self.client).unwrap();
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.flush();
+        }
 
         for msg in msgs {
             self.handle_msg(msg).await;
         }
     }
 
-    async fn render(&self) {
+    // plays the demo through handle_msg, like the messages came from the network
+    async fn handle_demo(&mut self, delta: f32, typing: bool) {
+        let demo = self.demo.as_mut().unwrap();
+
+        if !typing {
+            if is_key_pressed(KeyCode::P) {
+                demo.paused = !demo.paused;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                demo.speed = (demo.speed * 2.0).min(MAX_DEMO_SPEED);
+            }
+            if is_key_pressed(KeyCode::Down) {
+                demo.speed = (demo.speed / 2.0).max(1.0 / MAX_DEMO_SPEED);
+            }
+
+            let seek = if is_key_pressed(KeyCode::Right) {
+                DEMO_SEEK
+            } else if is_key_pressed(KeyCode::Left) {
+                -DEMO_SEEK
+            } else {
+                0.0
+            };
+            if seek != 0.0 && demo.seek(demo.time() + seek) {
+                self.reset();
+                self.transfers = TransferReceiver::new();
+                self.clock = ServerClock::default();
+            }
+        }
+
+        let demo = self.demo.as_mut().unwrap();
+        let mut msgs = Vec::new();
+        for frame in demo.advance(delta) {
+            match frame.msgs() {
+                Ok(frame_msgs) => msgs.extend(frame_msgs),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+
+        for msg in msgs {
+            // a demo recorded by a client has its player, the camera is free here
+            if matches!(msg, ServerMessage::AssignId(_)) {
+                continue;
+            }
+            if matches!(msg, ServerMessage::PhysicsDiff(_) | ServerMessage::Ecs(_)) {
+                self.last_snapshot = Some(Instant::now());
+            }
+            self.handle_msg(msg).await;
+        }
+    }
+
+    fn upload_meshes(&mut self) {
+        let meshes: Vec<(Entity, Mesh)> = self.shared.ecs.query::<&MeshWrapper>().without::<&Mesh>().iter()
+            .map(|(id, wrapper)| (id, wrapper.to_mesh()))
+            .collect();
+
+        for (id, mesh) in meshes {
+            self.shared.ecs.insert_one(id, mesh).unwrap();
+        }
+
+        for (model, parts) in self.models.drain() {
+            let meshes = parts.iter().map(|(wrapper, transform)| (wrapper.to_mesh(), *transform)).collect();
+            self.model_meshes.insert(model, meshes);
+        }
+    }
+
+    fn draw_loading_bar(&self, (received, total): (usize, usize)) {
+        let progress = received as f32 / total.max(1) as f32;
+        let (x, y) = (10.0, 50.0);
+        let (width, height) = (screen_width() - 20.0, 20.0);
+
+        draw_text("LOADING...", 10.0, 30.0, 30.0, WHITE);
+        draw_rectangle(x, y, width * progress, height, WHITE);
+        draw_rectangle_lines(x, y, width, height, 2.0, WHITE);
+
+        let text = format!("{} / {} KB", received / 1024, total / 1024);
+        draw_text(&text, x, y + height + 25.0, 25.0, WHITE);
+    }
+
+    async fn render(&self, camera: Vec3) {
         // rapier3d::geometry::TypedShape;
 
         clear_background(LIGHTGRAY);
@@ -313,13 +907,21 @@ impl Client {
         //     }
         // }
 
-        for (id, mesh) in self.shared.ecs.query::<&Mesh>().iter() {
+        for (id, (mesh, transform)) in self.shared.ecs.query::<(&Mesh, &Transform)>().iter() {
             if id != self.player {
+                unsafe { get_internal_gl() }.quad_gl.push_model_matrix(transform.matrix());
                 draw_mesh(mesh);
+                unsafe { get_internal_gl() }.quad_gl.pop_model_matrix();
             }
         }
 
+        let modeled = self.draw_models();
+
         for (_, col) in self.shared.physics.state.collider_set.iter() {
+            if !col.is_enabled() || col.parent().is_some_and(|parent| modeled.contains(&parent)) {
+                continue;
+            }
+
             let shape = col.shape().as_typed_shape();
             match shape {
                 TypedShape::TriMesh(trimesh) => {
@@ -335,8 +937,22 @@ impl Client {
             }
         }
     
+        for (_, flag) in self.shared.ecs.query::<&Flag>().iter() {
+            draw_cube(flag.pos, vec3(0.5, 1.5, 0.5), None, flag.team.color());
+        }
+
+        for (_, item) in self.shared.ecs.query::<&Item>().iter() {
+            if item.available() {
+                draw_cube(item.pos, vec3(0.5, 0.5, 0.5), None, item.kind.color());
+            }
+        }
+
         set_default_camera();
 
+        if self.shared.ecs.query::<&Water>().iter().any(|(_, water)| water.contains(camera)) {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.1, 0.3, 0.6, 0.4));
+        }
+
         let center = (screen_width()/2.0, screen_height()/2.0);
         let crosshair_size = 12.0;
         draw_line(center.0 - crosshair_size, center.1, center.0 + crosshair_size, center.1, 1.0, BLACK);
@@ -344,27 +960,294 @@ impl Client {
 
         draw_text("JUNGLEBEAST", 10.0, 30.0, 30.0, RED);
 
-        if let Some((handle, player)) = self.shared.ecs.query_one::<(&RigidBodyHandle, &Player)>(self.player).unwrap().get() {
+        let mut query = self.shared.ecs.query_one::<(&RigidBodyHandle, &Player)>(self.player).ok();
+        if let Some((handle, player)) = query.as_mut().and_then(|query| query.get()) {
             let pos = conv_vec_2(*self.shared.physics.get_rig(*handle).translation());
             let text = format!("fps: {}, hp: {} pos: {:.1}", get_fps(), player.hp(), pos);
             draw_text(&text, 10.0, 55.0, 30.0, GRAY);
+
+            let text = format!("armor: {} {}: {}", player.armor(), self.weapon.name(), player.ammo(self.weapon));
+            draw_text(&text, 10.0, screen_height() - 20.0, 30.0, GRAY);
+
+            if player.dead() {
+                let text = "YOU DIED - PRESS SPACE TO RESPAWN";
+                let size = measure_text(text, None, 40, 1.0);
+                draw_text(text, center.0 - size.width / 2.0, center.1 - 60.0, 40.0, RED);
+            }
+        } else {
+            let text = match self.view {
+                View::Follow(id) => format!("SPECTATING {}", self.player_name(id)),
+                View::FreeFly { .. } => "SPECTATING".to_string(),
+            };
+            draw_text(&text, 10.0, 55.0, 30.0, GRAY);
+            let hint = if self.demo.is_some() { "click: next player, right click: free fly" } else { "click: next player, right click: free fly, J: join" };
+            draw_text(hint, 10.0, screen_height() - 20.0, 25.0, GRAY);
+        }
+
+        if let Some(demo) = &self.demo {
+            self.draw_demo_state(demo);
+        } else if self.show_net_stats {
+            self.draw_net_stats();
+        }
+
+        self.draw_kill_feed();
+        self.draw_chat();
+        self.draw_match_state();
+
+        if is_key_down(KeyCode::Tab) {
+            self.draw_scoreboard();
+        }
+    }
+
+    // returns the bodies that got a model, they don't need their debug wireframe
+    fn draw_models(&self) -> Vec<RigidBodyHandle> {
+        let mut modeled = Vec::new();
+        let following = match self.view {
+            View::Follow(id) if self.local_body().is_none() => Some(id),
+            _ => None,
+        };
+
+        let mut query = self.shared.ecs.query::<(&ModelId, &Transform, &RigidBodyHandle, Option<&Player>)>();
+        for (id, (model, transform, handle, player)) in query.iter() {
+            let Some(parts) = self.model_meshes.get(model) else { continue };
+            if id == self.player || Some(id) == following || player.is_some_and(|player| player.dead()) {
+                continue;
+            }
+
+            // player bodies don't turn, the model looks where the player does
+            let mut matrix = transform.matrix();
+            if let Some(player) = player {
+                matrix *= Mat4::from_rotation_y(-player.cmd.yaw) * Mat4::from_scale(player.size() / PLAYER_SIZE);
+            }
+
+            for (mesh, part) in parts {
+                unsafe { get_internal_gl() }.quad_gl.push_model_matrix(matrix * part.matrix());
+                draw_mesh(mesh);
+                unsafe { get_internal_gl() }.quad_gl.pop_model_matrix();
+            }
+            modeled.push(*handle);
+        }
+
+        modeled
+    }
+
+    fn player_name(&self, id: Entity) -> String {
+        if id == self.player {
+            "you".to_string()
+        } else {
+            player_name(id)
+        }
+    }
+
+    // only the client cvars can be set here, the server ones go through rcon
+    fn set_cvar(&mut self, args: &str) -> String {
+        let Some((name, value)) = args.trim().split_once(' ') else {
+            return "usage: /set <name> <value>".to_string();
+        };
+
+        if !Cvars::info(name).is_some_and(|info| has_flag(info.flags, SAVED)) {
+            return format!("{} is not a client setting", name);
+        }
+
+        match self.shared.cvars.set(name, value.trim()) {
+            Ok(()) => {
+                self.shared.cvars.save_config(CONFIG_PATH);
+                format!("{} = {}", name, value.trim())
+            }
+            Err(err) => err,
+        }
+    }
+
+    // adds a line to the chat
+    fn print(&mut self, line: String) {
+        self.chat_log.push_back((Instant::now(), line));
+        if self.chat_log.len() > CHAT_LEN {
+            self.chat_log.pop_front();
+        }
+    }
+
+    fn draw_chat(&self) {
+        let typing = self.chat_input.is_some();
+        let lines: Vec<&String> = self.chat_log.iter()
+            .filter(|(time, _)| typing || time.elapsed() < CHAT_TIME)
+            .map(|(_, line)| line)
+            .collect();
+
+        let bottom = screen_height() - 60.0;
+        for (i, line) in lines.iter().rev().enumerate() {
+            draw_text(line, 10.0, bottom - i as f32 * 20.0, 20.0, BLACK);
+        }
+
+        if let Some(input) = &self.chat_input {
+            let prompt = if input.team_only { "say (team)" } else { "say" };
+            draw_text(format!("{}: {}_", prompt, input.text), 10.0, bottom + 20.0, 20.0, DARKBLUE);
+        }
+    }
+
+    fn draw_match_state(&self) {
+        let Some(state) = &self.match_state else { return };
+
+        let phase = match state.phase {
+            MatchPhase::Warmup => "warmup",
+            MatchPhase::Playing => "",
+            MatchPhase::Intermission { .. } => "intermission",
+        };
+        let time = state.time_remaining as u32;
+        let text = format!("{} {} {}:{:02}", state.mode, phase, time / 60, time % 60);
+        let size = measure_text(&text, None, 25, 1.0);
+        draw_text(&text, (screen_width() - size.width) / 2.0, 25.0, 25.0, BLACK);
+
+        if self.shared.ecs.query::<&Team>().iter().next().is_some() {
+            let text = format!("red {} - {} blue", state.team_score(Team::Red), state.team_score(Team::Blue));
+            let size = measure_text(&text, None, 25, 1.0);
+            draw_text(&text, (screen_width() - size.width) / 2.0, 50.0, 25.0, BLACK);
+        }
+
+        if let MatchPhase::Intermission { winner } = state.phase {
+            let text = match winner {
+                Some(Winner::Player(id)) => format!("{} won the match", self.player_name(id)),
+                Some(Winner::Team(team)) => format!("{} team won the match", team.name()),
+                None => "the match is a draw".to_string(),
+            };
+            let size = measure_text(&text, None, 40, 1.0);
+            draw_text(&text, (screen_width() - size.width) / 2.0, screen_height() / 3.0, 40.0, RED);
         }
     }
-}
 
-fn conf() -> Conf {
-    Conf {
-        window_title: String::from("JUNGLEBEAST"),
-        window_width: 1260,
-        window_height: 768,
-        fullscreen: false,
-        ..Default::default()
+    fn draw_kill_feed(&self) {
+        let lines = self.kill_feed.iter()
+            .filter(|(time, _)| time.elapsed() < KILL_FEED_TIME)
+            .map(|(_, event)| {
+                if event.suicide() {
+                    format!("{} killed themselves", self.player_name(event.victim))
+                } else {
+                    format!("{} [{}{}] {}",
+                        self.player_name(event.attacker),
+                        event.weapon.name(),
+                        if event.headshot { ", headshot" } else { "" },
+                        self.player_name(event.victim))
+                }
+            });
+
+        for (i, line) in lines.enumerate() {
+            let size = measure_text(&line, None, 20, 1.0);
+            draw_text(&line, screen_width() - size.width - 10.0, 25.0 + i as f32 * 20.0, 20.0, BLACK);
+        }
     }
+
+    fn draw_scoreboard(&self) {
+        let mut scores: Vec<(Entity, Score)> = self.shared.ecs.query::<&Score>().iter()
+            .map(|(id, score)| (id, score.clone()))
+            .collect();
+        scores.sort_by(|a, b| b.1.frags.cmp(&a.1.frags).then(a.1.deaths.cmp(&b.1.deaths)));
+
+        let (width, row) = (500.0, 25.0);
+        let height = row * (scores.len() + 1) as f32 + 10.0;
+        let x = (screen_width() - width) / 2.0;
+        let y = 100.0;
+
+        draw_rectangle(x, y, width, height, Color::new(0.0, 0.0, 0.0, 0.6));
+
+        let columns = [0.0, 200.0, 270.0, 340.0, 420.0];
+        let header = ["name", "frags", "deaths", "suicides", "ping"];
+        for (column, text) in columns.iter().zip(header) {
+            draw_text(text, x + 10.0 + column, y + row, 20.0, GRAY);
+        }
+
+        for (i, (id, score)) in scores.iter().enumerate() {
+            let color = match self.shared.ecs.get::<&Team>(*id) {
+                Ok(team) => team.color(),
+                Err(_) if *id == self.player => YELLOW,
+                Err(_) => WHITE,
+            };
+            let texts = [
+                self.player_name(*id),
+                score.frags.to_string(),
+                score.deaths.to_string(),
+                score.suicides.to_string(),
+                score.ping.to_string(),
+            ];
+            for (column, text) in columns.iter().zip(texts) {
+                draw_text(&text, x + 10.0 + column, y + row * (i + 2) as f32, 20.0, color);
+            }
+        }
+    }
+
+    fn draw_demo_state(&self, demo: &DemoPlayer) {
+        let state = if demo.paused { "paused".to_string() } else { format!("x{}", demo.speed) };
+        let text = format!("DEMO {:.1} / {:.1}s {}", demo.time(), demo.duration(), state);
+        draw_text(&text, 10.0, 80.0, 25.0, DARKGRAY);
+        draw_text("P: pause, left/right: seek, up/down: speed", 10.0, 100.0, 20.0, DARKGRAY);
+    }
+
+    fn draw_net_stats(&self) {
+        let info = self.client.network_info();
+        let snapshot_age = match self.last_snapshot {
+            Some(time) => format!("{} ms", time.elapsed().as_millis()),
+            None => "-".to_string(),
+        };
+
+        let lines = [
+            format!("rtt: {:.0} ms", info.rtt * 1000.0),
+            format!("tick: {}", self.server_tick),
+            format!("loss: {:.1}%", info.packet_loss * 100.0),
+            format!("up: {:.1} KB/s", info.bytes_sent_per_second / 1024.0),
+            format!("down: {:.1} KB/s", info.bytes_received_per_second / 1024.0),
+            format!("snapshot age: {}", snapshot_age),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, 10.0, 80.0 + i as f32 * 20.0, 20.0, DARKGRAY);
+        }
+    }
+}
+
+// missing ones are only drawn as wireframes
+fn load_models() -> HashMap<ModelId, Vec<(MeshWrapper, Transform)>> {
+    ModelId::all().into_iter()
+        .filter_map(|model| match Shared::load_model(&model.path()) {
+            Ok(parts) => Some((model, parts)),
+            Err(err) => {
+                println!("{}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct ClientArgs {
+    #[command(subcommand)]
+    command: Option<ClientCommand>,
+    #[command(flatten)]
+    connect: Option<ConnectArgs>,
+}
+
+#[derive(Subcommand)]
+enum ClientCommand {
+    #[command(about = "play back a demo")]
+    Play(PlayArgs),
+    #[command(about = "run a server in the client and join it")]
+    Host(ServerArgs),
+}
+
+#[derive(Parser)]
+struct ConnectArgs {
+    #[command(flatten)]
+    common: Args,
+    #[arg(long, help = "join as a spectator, press J to join the game later")]
+    spectate: bool,
+    #[arg(long, help = "used by /rcon <command> in the chat")]
+    rcon_password: Option<String>,
+    #[arg(long, value_name = "PATH", help = "record a demo of what the server sends")]
+    record: Option<String>,
 }
 
-#[macroquad::main(conf)]
-async fn main() {
-    let args = Args::parse();
-    let mut client = Client::create(args.addr);
-    client.start().await;
+#[derive(Parser)]
+struct PlayArgs {
+    #[arg(help = "path of the demo")]
+    demo: String,
+    #[arg(long, default_value_t = 1.0, help = "playback speed")]
+    speed: f32,
 }
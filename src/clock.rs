@@ -0,0 +1,73 @@
+use crate::shared::*;
+use crate::interpolation::*;
+
+// the client's estimate of the server time, in seconds of server ticks.
+// a pong comes back stamped with the tick it left at, half of the round trip later
+// the server is that much further. times are local seconds, the caller decides what
+// local means, the demo player has its own
+
+// seconds
+pub const PING_INTERVAL: f64 = 1.0;
+
+// an estimate further off than this is replaced instead of smoothed,
+// after a lag spike or when a demo seeks
+const SNAP_THRESHOLD: f64 = 0.25;
+
+const SMOOTHING: f64 = 0.1;
+
+#[derive(Default)]
+pub struct ServerClock {
+    offset: Option<f64>, // server time minus local time
+    rtt: f64,
+    last_ping: Option<f64>,
+}
+
+impl ServerClock {
+    // a ping to send, if the last one is old enough
+    pub fn ping(&mut self, now: f64) -> Option<ClientMessage> {
+        if self.last_ping.is_some_and(|last| now - last < PING_INTERVAL) {
+            return None;
+        }
+
+        self.last_ping = Some(now);
+        Some(ClientMessage::Ping(now))
+    }
+
+    // the answer to the ping sent at `sent`, server_time is the tick it was stamped with
+    pub fn pong(&mut self, sent: f64, now: f64, server_time: f64) {
+        let rtt = (now - sent).max(0.0);
+        self.rtt = match self.offset {
+            Some(_) => self.rtt + (rtt - self.rtt) * SMOOTHING,
+            None => rtt,
+        };
+        self.sync(now, server_time + rtt / 2.0);
+    }
+
+    // the server is at server_time right now
+    pub fn sync(&mut self, now: f64, server_time: f64) {
+        let offset = server_time - now;
+        self.offset = Some(match self.offset {
+            Some(old) if (offset - old).abs() < SNAP_THRESHOLD => old + (offset - old) * SMOOTHING,
+            _ => offset,
+        });
+    }
+
+    pub fn rtt(&self) -> f64 {
+        self.rtt
+    }
+
+    // None until the first pong
+    pub fn server_time(&self, now: f64) -> Option<f64> {
+        self.offset.map(|offset| now + offset)
+    }
+
+    // when input sent now arrives, prediction runs this far ahead
+    pub fn prediction_time(&self, now: f64) -> Option<f64> {
+        self.server_time(now).map(|time| time + self.rtt / 2.0)
+    }
+
+    // remote bodies are shown this far behind, so there is a snapshot on both sides
+    pub fn interpolation_time(&self, now: f64) -> Option<f64> {
+        self.server_time(now).map(|time| time - INTERPOLATION_DELAY.as_secs_f64())
+    }
+}
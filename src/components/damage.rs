@@ -0,0 +1,53 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Weapon {
+    Gun,
+    Rail,
+}
+
+impl Weapon {
+    pub const ALL: [Weapon; 2] = [Weapon::Gun, Weapon::Rail];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn damage(&self, cvars: &Cvars) -> u16 {
+        match self {
+            Weapon::Gun => cvars.sv_gun_damage,
+            Weapon::Rail => cvars.sv_rail_damage,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weapon::Gun => "gun",
+            Weapon::Rail => "rail",
+        }
+    }
+}
+
+pub const HEADSHOT_MULTIPLIER: u16 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub victim: Entity,
+    pub weapon: Weapon,
+    pub headshot: bool,
+}
+
+impl DamageEvent {
+    pub fn damage(&self, cvars: &Cvars) -> u16 {
+        if self.headshot {
+            self.weapon.damage(cvars).saturating_mul(HEADSHOT_MULTIPLIER)
+        } else {
+            self.weapon.damage(cvars)
+        }
+    }
+
+    pub fn suicide(&self) -> bool {
+        self.attacker == self.victim
+    }
+}
@@ -0,0 +1,32 @@
+use crate::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flag {
+    pub team: Team,
+    pub home: Vec3,
+    pub pos: Vec3,
+    pub carrier: Option<Entity>,
+    pub return_timer: f32, // counts down while dropped
+}
+
+impl Flag {
+    pub fn new(team: Team, home: Vec3) -> Self {
+        Self {
+            team,
+            home,
+            pos: home,
+            carrier: None,
+            return_timer: 0.0,
+        }
+    }
+
+    pub fn at_home(&self) -> bool {
+        self.carrier.is_none() && self.pos == self.home
+    }
+
+    pub fn reset(&mut self) {
+        self.pos = self.home;
+        self.carrier = None;
+        self.return_timer = 0.0;
+    }
+}
@@ -0,0 +1,74 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    #[serde(rename = "health_25")]
+    Health25,
+    #[serde(rename = "health_50")]
+    Health50,
+    #[serde(rename = "armor_50")]
+    Armor50,
+    #[serde(rename = "armor_100")]
+    Armor100,
+    AmmoGun,
+    AmmoRail,
+    WeaponRail,
+}
+
+impl ItemKind {
+    // seconds until it comes back, if the map doesn't say
+    pub fn default_respawn(&self) -> f32 {
+        match self {
+            ItemKind::Health25 | ItemKind::Health50 => 20.0,
+            ItemKind::Armor50 | ItemKind::Armor100 => 25.0,
+            ItemKind::AmmoGun | ItemKind::AmmoRail => 15.0,
+            ItemKind::WeaponRail => 30.0,
+        }
+    }
+
+    // returns false if the player doesn't need it, so it stays there
+    pub fn apply(&self, player: &mut Player) -> bool {
+        match self {
+            ItemKind::Health25 => player.heal(25),
+            ItemKind::Health50 => player.heal(50),
+            ItemKind::Armor50 => player.add_armor(50),
+            ItemKind::Armor100 => player.add_armor(100),
+            ItemKind::AmmoGun => player.add_ammo(Weapon::Gun, 25),
+            ItemKind::AmmoRail => player.add_ammo(Weapon::Rail, 5),
+            ItemKind::WeaponRail => player.give_weapon(Weapon::Rail, 10),
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            ItemKind::Health25 | ItemKind::Health50 => GREEN,
+            ItemKind::Armor50 | ItemKind::Armor100 => YELLOW,
+            ItemKind::AmmoGun | ItemKind::AmmoRail => BROWN,
+            ItemKind::WeaponRail => PURPLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub pos: Vec3,
+    pub respawn: f32,
+    pub timer: f32, // counts down while picked up
+}
+
+impl Item {
+    pub fn new(kind: ItemKind, pos: Vec3, respawn: Option<f32>) -> Self {
+        Self {
+            kind,
+            pos,
+            respawn: respawn.unwrap_or(kind.default_respawn()),
+            timer: 0.0,
+        }
+    }
+
+    pub fn available(&self) -> bool {
+        self.timer <= 0.0
+    }
+}
@@ -0,0 +1,31 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Winner {
+    Player(Entity),
+    Team(Team),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    Warmup,
+    Playing,
+    Intermission { winner: Option<Winner> }, // None is a draw
+}
+
+// replicated to the clients every snapshot
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchState {
+    pub mode: String,
+    pub phase: MatchPhase,
+    pub time_remaining: f32, // seconds left of the current phase, or 0 if unlimited
+    pub score_limit: u32,    // frags or captures, 0 is unlimited
+    pub team_scores: [i32; 2],
+}
+
+impl MatchState {
+    pub fn team_score(&self, team: Team) -> i32 {
+        self.team_scores[team.index()]
+    }
+}
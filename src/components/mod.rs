@@ -1,11 +1,41 @@
 mod player;
 pub use player::*;
 
+mod user_cmd;
+pub use user_cmd::*;
+
 mod model;
 pub use model::*;
 
+mod transform;
+pub use transform::*;
+
 mod point;
 pub use point::*;
 
 mod properties;
 pub use properties::*;
+
+mod score;
+pub use score::*;
+
+mod damage;
+pub use damage::*;
+
+mod team;
+pub use team::*;
+
+mod match_state;
+pub use match_state::*;
+
+mod flag;
+pub use flag::*;
+
+mod item;
+pub use item::*;
+
+mod water;
+pub use water::*;
+
+mod mover;
+pub use mover::*;
@@ -52,13 +52,39 @@ impl MeshWrapper {
             None
         };
 
-        let mesh = Mesh {
+        Mesh {
             vertices: self.vertices.iter()
                 .map(|v| v.into()).collect(),
             indices: (*self.indices).to_vec(),
             texture,
-        };
+        }
+    }
+}
+
+pub const GIB_MODELS: u8 = 3;
+
+// the glTF model drawn at an entity, the client loads them from models/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModelId {
+    Player,
+    Gib(u8),
+}
+
+impl ModelId {
+    pub fn all() -> Vec<ModelId> {
+        std::iter::once(ModelId::Player)
+            .chain((0..GIB_MODELS).map(ModelId::Gib))
+            .collect()
+    }
 
-        mesh
+    pub fn random_gib() -> Self {
+        ModelId::Gib(gen_range(0, GIB_MODELS))
+    }
+
+    pub fn path(&self) -> String {
+        match self {
+            ModelId::Player => "models/player.glb".to_string(),
+            ModelId::Gib(index) => format!("models/gib_{}.glb", index),
+        }
     }
 }
@@ -0,0 +1,67 @@
+use crate::*;
+
+// how a mover is set up in the map, {"mover": {"path": [[0, 3, 0]], "speed": 2, "wait": 1}}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoverProps {
+    pub path: Vec<Vec3>, // offsets from where it starts
+    pub speed: f32,
+    #[serde(default)]
+    pub wait: f32, // seconds it stops at every point
+    pub trigger: Option<f32>, // only leaves the start when a player comes this close
+}
+
+// a platform, door or elevator that goes from its start through the path
+// and straight back to the start, over and over.
+// only the server has it, the clients just get the body
+#[derive(Clone, Debug)]
+pub struct Mover {
+    pub points: Vec<Vec3>, // the start and then the path
+    pub speed: f32,
+    pub wait: f32,
+    pub sensor: Option<ColliderHandle>,
+    pub pos: Vec3,
+    pub target: usize, // index of the point it goes to
+    pub timer: f32, // counts down while waiting at a point
+}
+
+impl Mover {
+    pub fn new(start: Vec3, props: &MoverProps, sensor: Option<ColliderHandle>) -> Self {
+        Self {
+            points: std::iter::once(start).chain(props.path.iter().map(|offset| start + *offset)).collect(),
+            speed: props.speed,
+            wait: props.wait,
+            sensor,
+            pos: start,
+            target: 1 % (props.path.len() + 1),
+            timer: 0.0,
+        }
+    }
+
+    // triggered ones wait at the start until they are triggered again
+    pub fn at_rest(&self) -> bool {
+        self.sensor.is_some() && self.target == 1 && self.pos == self.points[0]
+    }
+
+    // where it is after dt seconds
+    pub fn advance(&mut self, dt: f32, triggered: bool) -> Vec3 {
+        if self.timer > 0.0 {
+            self.timer -= dt;
+            return self.pos;
+        }
+        if self.at_rest() && !triggered {
+            return self.pos;
+        }
+
+        let to = self.points[self.target];
+        let step = self.speed * dt;
+        if self.pos.distance(to) <= step {
+            self.pos = to;
+            self.target = (self.target + 1) % self.points.len();
+            self.timer = self.wait;
+        } else {
+            self.pos += (to - self.pos).normalize() * step;
+        }
+
+        self.pos
+    }
+}
@@ -1,25 +1,58 @@
 use crate::*;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct MoveState {
-    pub forward: bool,
-    pub back: bool,
-    pub left: bool,
-    pub right: bool,
-    pub jump: bool,
+pub const PLAYER_SIZE: Vec3 = vec3(1.0, 2.0, 1.0);
+pub const CROUCH_SIZE: Vec3 = vec3(1.0, 1.2, 1.0);
+
+// of the camera, from the feet
+pub const EYE_HEIGHT: f32 = 1.6;
+pub const CROUCH_EYE_HEIGHT: f32 = 0.9;
+
+pub const MAX_HP: u16 = 100;
+pub const MAX_ARMOR: u16 = 100;
+pub const MAX_AMMO: [u16; 2] = [100, 25]; // indexed by Weapon::index
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PlayerState {
+    Alive,
+    Dead { respawn_at: f32 }, // server time in seconds
+}
+
+// there are no names yet
+pub fn player_name(id: Entity) -> String {
+    format!("player {}", id.id())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     hp: u16,
-    pub moves: MoveState,
+    armor: u16,
+    weapons: Vec<Weapon>,
+    ammo: [u16; 2],
+    pub cmd: UserCmd, // the one being played
+    pub crouched: bool,
+    pub swimming: bool,
+    jump: Option<f32>, // seconds since it was pressed, until it's used or too old
+    pub state: PlayerState,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Player {
     pub fn new() -> Self {
         Self {
-            hp: 100,
-            moves: MoveState::default(),
+            hp: MAX_HP,
+            armor: 0,
+            weapons: vec![Weapon::Gun],
+            ammo: [50, 0],
+            cmd: UserCmd::default(),
+            crouched: false,
+            swimming: false,
+            jump: None,
+            state: PlayerState::Alive,
         }
     }
 
@@ -27,31 +60,120 @@ impl Player {
         self.hp
     }
 
+    pub fn armor(&self) -> u16 {
+        self.armor
+    }
+
+    pub fn ammo(&self, weapon: Weapon) -> u16 {
+        self.ammo[weapon.index()]
+    }
+
+    pub fn has_weapon(&self, weapon: Weapon) -> bool {
+        self.weapons.contains(&weapon)
+    }
+
+    // armor takes the damage first
     pub fn hurt(&mut self, damage: u16) {
-        self.hp = self.hp.saturating_sub(damage);
+        let absorbed = damage.min(self.armor);
+        self.armor -= absorbed;
+        self.hp = self.hp.saturating_sub(damage - absorbed);
+    }
+
+    // the following return false if nothing changed
+
+    pub fn heal(&mut self, amount: u16) -> bool {
+        if self.hp >= MAX_HP {
+            return false;
+        }
+        self.hp = (self.hp + amount).min(MAX_HP);
+        true
+    }
+
+    pub fn add_armor(&mut self, amount: u16) -> bool {
+        if self.armor >= MAX_ARMOR {
+            return false;
+        }
+        self.armor = (self.armor + amount).min(MAX_ARMOR);
+        true
+    }
+
+    pub fn add_ammo(&mut self, weapon: Weapon, amount: u16) -> bool {
+        let max = MAX_AMMO[weapon.index()];
+        let ammo = &mut self.ammo[weapon.index()];
+        if *ammo >= max {
+            return false;
+        }
+        *ammo = (*ammo + amount).min(max);
+        true
+    }
+
+    pub fn give_weapon(&mut self, weapon: Weapon, ammo: u16) -> bool {
+        let new = !self.has_weapon(weapon);
+        if new {
+            self.weapons.push(weapon);
+        }
+        self.add_ammo(weapon, ammo) || new
+    }
+
+    pub fn use_ammo(&mut self, weapon: Weapon) -> bool {
+        if !self.has_weapon(weapon) || self.ammo(weapon) == 0 {
+            return false;
+        }
+        self.ammo[weapon.index()] -= 1;
+        true
+    }
+
+    pub fn size(&self) -> Vec3 {
+        if self.crouched { CROUCH_SIZE } else { PLAYER_SIZE }
+    }
+
+    pub fn eye_height(&self) -> f32 {
+        if self.crouched { CROUCH_EYE_HEIGHT } else { EYE_HEIGHT }
     }
 
     pub fn dead(&self) -> bool {
-        self.hp == 0
+        matches!(self.state, PlayerState::Dead { .. })
     }
 
-    pub fn reset_hp(&mut self) {
-        self.hp = 100;
+    pub fn die(&mut self, respawn_at: f32) {
+        self.hp = 0;
+        self.cmd.stop();
+        self.jump = None;
+        self.state = PlayerState::Dead { respawn_at };
+    }
+
+    pub fn can_respawn(&self, time: f32) -> bool {
+        match self.state {
+            PlayerState::Dead { respawn_at } => time >= respawn_at,
+            PlayerState::Alive => false,
+        }
     }
-}
 
-impl MoveState {
-    pub fn reset(&mut self) {
-        *self = Self::default();
+    // back to the starting loadout
+    pub fn respawn(&mut self) {
+        *self = Self::new();
     }
 
-    pub fn set_jump(&mut self) {
-        self.jump = true;
+    // a jump needs the button to be pressed again, holding it jumps once
+    pub fn set_cmd(&mut self, cmd: UserCmd) {
+        if cmd.pressed(BUTTON_JUMP) && !self.cmd.pressed(BUTTON_JUMP) {
+            self.jump = Some(0.0);
+        }
+        self.cmd = cmd;
+    }
+
+    // a press still counts for `buffer` seconds, so pressing just before landing jumps
+    pub fn buffered_jump(&mut self, dt: f32, buffer: f32) -> bool {
+        let Some(age) = self.jump else { return false };
+        if age > buffer {
+            self.jump = None;
+            return false;
+        }
+        self.jump = Some(age + dt);
+        true
     }
 
-    pub fn get_jump(&mut self) -> bool {
-        let jump = self.jump;
-        self.jump = false;
-        jump
+    pub fn clear_jump(&mut self) {
+        self.jump = None;
     }
 }
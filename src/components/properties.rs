@@ -4,4 +4,10 @@ use crate::*;
 pub struct Properties {
     pub spawn: Option<bool>,
     pub launch: Option<Vec3>,
+    pub team: Option<Team>, // team spawn points
+    pub flag: Option<Team>, // ctf flag bases
+    pub item: Option<ItemKind>,
+    pub respawn: Option<f32>, // item respawn time in seconds
+    pub water: Option<bool>, // swimmable volume instead of solid geometry
+    pub mover: Option<MoverProps>,
 }
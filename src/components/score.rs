@@ -0,0 +1,9 @@
+use crate::*;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub frags: i32,
+    pub deaths: u32,
+    pub suicides: u32,
+    pub ping: u32, // ms
+}
@@ -0,0 +1,33 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Red, Team::Blue];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Team::Red => 0,
+            Team::Blue => 1,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Team::Red => "red",
+            Team::Blue => "blue",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Team::Red => RED,
+            Team::Blue => BLUE,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::*;
+
+// where a mesh is drawn, its vertices are relative to this
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub pos: Vec3,
+    pub rot: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn new(pos: Vec3) -> Self {
+        Self {
+            pos,
+            rot: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+
+    // the scale stays, bodies don't have one
+    pub fn set_isometry(&mut self, iso: &Isometry<f32>) {
+        self.pos = conv_vec_2(iso.translation.vector);
+        self.rot = conv_quat_2(iso.rotation);
+    }
+
+    pub fn isometry(&self) -> Isometry<f32> {
+        Isometry::from_parts(conv_vec_1(self.pos).into(), conv_quat_1(self.rot))
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rot, self.pos)
+    }
+}
@@ -0,0 +1,115 @@
+use crate::*;
+
+use std::collections::VecDeque;
+
+// the input of a player for one server tick
+
+pub const BUTTON_FIRE: u8 = 1 << 0;
+pub const BUTTON_JUMP: u8 = 1 << 1;
+pub const BUTTON_CROUCH: u8 = 1 << 2;
+pub const BUTTON_USE: u8 = 1 << 3;
+
+// sent with every command, so a lost packet doesn't lose any input
+pub const REDUNDANT_CMDS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserCmd {
+    pub tick: Tick, // the server tick it is meant for
+    pub forward: f32, // -1 to 1
+    pub side: f32, // -1 to 1, right is positive
+    pub yaw: f32,
+    pub pitch: f32,
+    pub buttons: u8,
+    pub weapon: Weapon,
+}
+
+impl Default for UserCmd {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            forward: 0.0,
+            side: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            buttons: 0,
+            weapon: Weapon::Gun,
+        }
+    }
+}
+
+impl UserCmd {
+    pub fn pressed(&self, button: u8) -> bool {
+        has_flag(self.buttons, button)
+    }
+
+    pub fn press(&mut self, button: u8) {
+        self.buttons |= button;
+    }
+
+    // stops moving, but keeps looking the same way
+    pub fn stop(&mut self) {
+        self.forward = 0.0;
+        self.side = 0.0;
+        self.buttons = 0;
+    }
+
+    // where the view points, without the pitch
+    pub fn flat_forward(&self) -> Vec3 {
+        vec3(self.yaw.cos(), 0.0, self.yaw.sin())
+    }
+
+    // the commands come from the clients, so nothing is trusted
+    fn sanitize(&mut self) {
+        let axis = |value: f32| if value.is_finite() { value.clamp(-1.0, 1.0) } else { 0.0 };
+        self.forward = axis(self.forward);
+        self.side = axis(self.side);
+        if !self.yaw.is_finite() { self.yaw = 0.0; }
+        self.pitch = if self.pitch.is_finite() { self.pitch.clamp(-1.5, 1.5) } else { 0.0 };
+    }
+}
+
+// commands further ahead than this are treated as if they were for this tick
+const MAX_LEAD: Tick = 30;
+
+const MAX_BUFFERED: usize = 64;
+
+// the commands of one client, waiting for their tick on the server
+#[derive(Default)]
+pub struct CmdBuffer {
+    cmds: VecDeque<UserCmd>,
+    newest: Option<Tick>,
+}
+
+impl CmdBuffer {
+    // every command arrives several times, only the first copy is kept
+    pub fn push(&mut self, mut cmd: UserCmd, current_tick: Tick) {
+        cmd.tick = cmd.tick.min(current_tick + MAX_LEAD);
+        if self.newest.is_some_and(|newest| cmd.tick <= newest) {
+            return;
+        }
+
+        cmd.sanitize();
+        self.newest = Some(cmd.tick);
+        self.cmds.push_back(cmd);
+        if self.cmds.len() > MAX_BUFFERED {
+            self.cmds.pop_front();
+        }
+    }
+
+    // the command for this tick. late ones are merged into the newest,
+    // so a button pressed in a late command still counts.
+    // None if nothing new is due, the player keeps the last one
+    pub fn take(&mut self, tick: Tick) -> Option<UserCmd> {
+        let mut taken: Option<UserCmd> = None;
+
+        while self.cmds.front().is_some_and(|cmd| cmd.tick <= tick) {
+            let mut cmd = self.cmds.pop_front().unwrap();
+            if let Some(older) = taken {
+                cmd.buttons |= older.buttons;
+            }
+            taken = Some(cmd);
+        }
+
+        taken
+    }
+}
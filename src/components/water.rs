@@ -0,0 +1,28 @@
+use crate::*;
+
+// a volume players swim in, the bounds of a map mesh with {"water": true}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Water {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Water {
+    pub fn around(mesh: &MeshWrapper, transform: &Transform) -> Self {
+        let matrix = transform.matrix();
+        let positions = mesh.vertices.iter().map(|vertex| matrix.transform_point3(vertex.position));
+
+        Self {
+            min: positions.clone().fold(Vec3::INFINITY, Vec3::min),
+            max: positions.fold(Vec3::NEG_INFINITY, Vec3::max),
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+pub fn in_water(waters: &[Water], point: Vec3) -> bool {
+    waters.iter().any(|water| water.contains(point))
+}
@@ -0,0 +1,69 @@
+use crate::shared::*;
+
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver};
+
+// commands of the server console, typed on stdin or sent over rcon
+
+pub enum Command {
+    Help,
+    Status,
+    Kick(ClientId),
+    Map(String),
+    Say(String),
+    Cvars,
+    Set(String, String),
+    Quit,
+}
+
+pub const HELP: &str = "\
+status              list the players
+kick <id>           disconnect a client (or remove a bot)
+map <path>          change the map
+say <text>          send a message to everyone
+cvars               list the settings
+set <name> <value>  change a setting
+quit                stop the server";
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match name {
+            "help" => Ok(Command::Help),
+            "status" => Ok(Command::Status),
+            "kick" => rest.parse()
+                .map(Command::Kick)
+                .map_err(|_| "usage: kick <id>".to_string()),
+            "map" if !rest.is_empty() => Ok(Command::Map(rest.to_string())),
+            "map" => Err("usage: map <path>".to_string()),
+            "say" if !rest.is_empty() => Ok(Command::Say(rest.to_string())),
+            "say" => Err("usage: say <text>".to_string()),
+            "cvars" => Ok(Command::Cvars),
+            "set" => match rest.split_once(' ') {
+                Some((name, value)) => Ok(Command::Set(name.to_string(), value.trim().to_string())),
+                None => Err("usage: set <name> <value>".to_string()),
+            },
+            "quit" => Ok(Command::Quit),
+            _ => Err(format!("unknown command: {}, try help", name)),
+        }
+    }
+}
+
+// stdin is read on its own thread, so the server loop never blocks on it
+pub fn read_stdin() -> Receiver<String> {
+    let (sender, receiver) = channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if !line.trim().is_empty() && sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
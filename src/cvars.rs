@@ -0,0 +1,148 @@
+use crate::shared::*;
+
+use std::fs;
+
+// runtime settings, "sv_" ones are decided by the server and "cl_" ones by each client
+
+pub const REPLICATED: u8 = 1 << 0; // sent from the server to the clients
+pub const SAVED: u8 = 1 << 1;      // client side, stored in the config file
+
+pub const CONFIG_PATH: &str = "client.cfg";
+
+pub fn has_flag(flags: u8, flag: u8) -> bool {
+    flags & flag != 0
+}
+
+pub struct CvarInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub flags: u8,
+}
+
+macro_rules! cvars {
+    ($($name:ident: $t:ty = $default:expr, $min:expr, $max:expr, $flags:expr, $description:literal;)*) => {
+        #[derive(Clone, Debug, Serialize, Deserialize)]
+        pub struct Cvars {
+            $(pub $name: $t,)*
+        }
+
+        impl Default for Cvars {
+            fn default() -> Self {
+                Self {
+                    $($name: $default,)*
+                }
+            }
+        }
+
+        impl Cvars {
+            pub const LIST: &[CvarInfo] = &[
+                $(CvarInfo { name: stringify!($name), description: $description, flags: $flags },)*
+            ];
+
+            pub fn get(&self, name: &str) -> Option<String> {
+                match name {
+                    $(stringify!($name) => Some(self.$name.to_string()),)*
+                    _ => None,
+                }
+            }
+
+            // parses and checks the bounds, the flags are up to the caller
+            pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+                match name {
+                    $(stringify!($name) => {
+                        let value: $t = value.parse().map_err(|_| format!("invalid value for {}: {}", name, value))?;
+                        if !($min..=$max).contains(&value) {
+                            return Err(format!("{} must be between {} and {}", name, $min, $max));
+                        }
+                        self.$name = value;
+                        Ok(())
+                    })*
+                    _ => Err(format!("unknown cvar: {}", name)),
+                }
+            }
+
+            // takes the server side values from the server
+            pub fn replicate(&mut self, server: &Cvars) {
+                $(if has_flag($flags, REPLICATED) {
+                    self.$name = server.$name;
+                })*
+            }
+        }
+    }
+}
+
+cvars! {
+    sv_gravity: f32 = 9.81, 0.0, 100.0, REPLICATED, "downwards acceleration";
+    sv_physics_step: f32 = 1.0 / 60.0, 0.001, 0.1, REPLICATED, "seconds per physics step";
+    sv_tick_rate: u32 = 30, 1, 240, REPLICATED, "server updates per second";
+    sv_move_speed: f32 = 6.0, 0.0, 50.0, REPLICATED, "walking speed";
+    sv_jump_height: f32 = 1.25, 0.0, 10.0, REPLICATED, "how high a jump goes";
+    sv_accelerate: f32 = 10.0, 0.0, 100.0, REPLICATED, "acceleration on the ground";
+    sv_air_accelerate: f32 = 10.0, 0.0, 100.0, REPLICATED, "acceleration in the air";
+    sv_air_speed: f32 = 0.6, 0.0, 50.0, REPLICATED, "wish speed cap in the air, low values make strafe jumping work";
+    sv_friction: f32 = 6.0, 0.0, 100.0, REPLICATED, "ground friction";
+    sv_stop_speed: f32 = 2.0, 0.0, 50.0, REPLICATED, "below this speed friction acts as if it was this speed";
+    sv_jump_buffer: f32 = 0.1, 0.0, 1.0, REPLICATED, "seconds a jump pressed in the air is remembered";
+    sv_auto_hop: bool = false, false, true, REPLICATED, "holding jump keeps jumping";
+    sv_step_height: f32 = 0.5, 0.0, 2.0, REPLICATED, "highest ledge walked up without jumping";
+    sv_crouch_speed: f32 = 3.0, 0.0, 50.0, REPLICATED, "walking speed while crouched";
+    sv_swim_speed: f32 = 4.0, 0.0, 50.0, REPLICATED, "swimming speed";
+    sv_water_friction: f32 = 3.0, 0.0, 100.0, REPLICATED, "how quickly water slows down swimmers";
+    sv_buoyancy: f32 = 0.9, 0.0, 2.0, REPLICATED, "part of the gravity water pushes back, above 1 floats up";
+    sv_gun_damage: u16 = 20, 0, 1000, REPLICATED, "damage of a gun hit";
+    sv_rail_damage: u16 = 80, 0, 1000, REPLICATED, "damage of a rail hit";
+    sv_warmup: f32 = 10.0, 0.0, 3600.0, REPLICATED, "warmup length in seconds";
+    sv_time_limit: f32 = 600.0, 0.0, 36000.0, REPLICATED, "match length in seconds, 0 is unlimited";
+    sv_intermission: f32 = 10.0, 0.0, 3600.0, REPLICATED, "intermission length in seconds";
+    sv_score_limit: i32 = -1, -1, 1000, REPLICATED, "frags or captures needed to win, 0 is unlimited, -1 uses the default of the mode";
+    cl_look_speed: f32 = 0.1, 0.01, 1.0, SAVED, "mouse sensitivity";
+    cl_fov: f32 = 2.05, 0.5, 3.0, SAVED, "vertical field of view in radians";
+}
+
+impl Cvars {
+    pub fn info(name: &str) -> Option<&'static CvarInfo> {
+        Self::LIST.iter().find(|info| info.name == name)
+    }
+
+    pub fn jump_speed(&self) -> f32 {
+        (2.0 * self.sv_gravity * self.sv_jump_height).sqrt()
+    }
+
+    // "name=value" pairs from the command line
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        for arg in args {
+            let result = match arg.split_once('=') {
+                Some((name, value)) => self.set(name, value),
+                None => Err("expected name=value".to_string()),
+            };
+            result.map_err(|err| format!("--set {}: {}", arg, err))?;
+        }
+        Ok(())
+    }
+
+    // the config file has one "name value" per line
+    pub fn load_config(&mut self, path: &str) {
+        let Ok(config) = fs::read_to_string(path) else { return };
+
+        for line in config.lines() {
+            let Some((name, value)) = line.trim().split_once(' ') else { continue };
+            if !Self::info(name).is_some_and(|info| has_flag(info.flags, SAVED)) {
+                continue;
+            }
+            if let Err(err) = self.set(name, value.trim()) {
+                eprintln!("{}: {}", path, err);
+            }
+        }
+    }
+
+    pub fn save_config(&self, path: &str) {
+        let config: String = Self::LIST.iter()
+            .filter(|info| has_flag(info.flags, SAVED))
+            .map(|info| format!("{} {}\n", info.name, self.get(info.name).unwrap()))
+            .collect();
+
+        if let Err(err) = fs::write(path, config) {
+            eprintln!("failed to save {}: {}", path, err);
+        }
+    }
+}
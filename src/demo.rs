@@ -0,0 +1,146 @@
+use crate::shared::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+// a demo is every batch of server messages with the time it was sent or received at,
+// stored as the time, the length and the serialized ServerMessages
+
+pub struct DemoRecorder {
+    file: BufWriter<File>,
+    start: Instant,
+    failed: bool, // stops recording after the first write error
+}
+
+impl DemoRecorder {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("failed to create {}: {}", path, err))?;
+        println!("recording demo to {}", path);
+
+        Ok(Self {
+            file: BufWriter::new(file),
+            start: Instant::now(),
+            failed: false,
+        })
+    }
+
+    pub fn record(&mut self, msgs: &ServerMessages) {
+        if msgs.is_empty() || self.failed {
+            return;
+        }
+
+        let result = serialize(msgs).map_err(|err| err.to_string()).and_then(|data| {
+            let len = data.len() as u32;
+            self.file.write_all(&self.start.elapsed().as_secs_f32().to_le_bytes())
+                .and_then(|_| self.file.write_all(&len.to_le_bytes()))
+                .and_then(|_| self.file.write_all(&data))
+                .map_err(|err| err.to_string())
+        });
+        self.check(result);
+    }
+
+    // once per tick, so a killed server loses at most one tick
+    pub fn flush(&mut self) {
+        if self.failed {
+            return;
+        }
+
+        let result = self.file.flush().map_err(|err| err.to_string());
+        self.check(result);
+    }
+
+    fn check(&mut self, result: Result<(), String>) {
+        if let Err(err) = result {
+            eprintln!("stopped recording the demo: {}", err);
+            self.failed = true;
+        }
+    }
+}
+
+pub struct DemoFrame {
+    pub time: f32,
+    data: renet::Bytes,
+}
+
+impl DemoFrame {
+    // decoded when it is played, loading only reads the times
+    pub fn msgs(&self) -> Result<ServerMessages, String> {
+        deserialize(&self.data).map_err(|err| format!("bad demo frame at {:.1}s: {}", self.time, err))
+    }
+}
+
+pub struct DemoPlayer {
+    frames: Vec<DemoFrame>,
+    next: usize,
+    time: f32,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl DemoPlayer {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+        let mut frames = Vec::new();
+        let mut rest = data.as_slice();
+
+        // a truncated last frame is ignored, the recorder may have been killed mid write
+        while let Some((header, payload)) = rest.split_first_chunk::<8>() {
+            let time = f32::from_le_bytes(header[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+            if payload.len() < len {
+                break;
+            }
+            if !time.is_finite() || frames.last().is_some_and(|frame: &DemoFrame| time < frame.time) {
+                return Err(format!("{} is not a demo", path));
+            }
+
+            frames.push(DemoFrame { time, data: renet::Bytes::copy_from_slice(&payload[..len]) });
+            rest = &payload[len..];
+        }
+
+        println!("loaded demo {}, {} frames, {:.1}s", path, frames.len(), frames.last().map_or(0.0, |frame| frame.time));
+
+        Ok(Self {
+            frames,
+            next: 0,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+        })
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |frame| frame.time)
+    }
+
+    // the frames that are due after dt seconds of playback
+    pub fn advance(&mut self, dt: f32) -> &[DemoFrame] {
+        if !self.paused {
+            self.time = (self.time + dt * self.speed).min(self.duration());
+        }
+
+        let start = self.next;
+        while self.frames.get(self.next).is_some_and(|frame| frame.time <= self.time) {
+            self.next += 1;
+        }
+
+        &self.frames[start..self.next]
+    }
+
+    // messages can't be undone, so going back means starting over,
+    // returns true if the caller has to reset its state
+    pub fn seek(&mut self, time: f32) -> bool {
+        let time = time.clamp(0.0, self.duration());
+        let restart = time < self.time;
+        if restart {
+            self.next = 0;
+        }
+        self.time = time;
+        restart
+    }
+}
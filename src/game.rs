@@ -0,0 +1,688 @@
+use crate::shared::*;
+use crate::gamemode::*;
+use crate::navmesh::*;
+use crate::bot::*;
+use crate::clone_column;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+// the game logic of the server without the network, so it runs the same
+// in the server and in tests. messages for the clients are queued in outbox,
+// sending them is up to whoever owns the transport
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recipient {
+    All,
+    Client(ClientId),
+    Spectators, // connected clients without a player, only the transport knows them
+}
+
+pub struct Game {
+    pub shared: Shared,
+    pub client_ids: HashMap<ClientId, Entity>, // players and bots, spectators have none
+    pub time: f32, // seconds since start
+    pub current_tick: Tick,
+    pub current_match: Match,
+    pub navmesh: NavMesh,
+    pub bots: Vec<Bot>,
+    chat_limits: HashMap<ClientId, RateLimit>,
+    cmd_buffers: HashMap<ClientId, CmdBuffer>,
+    pub mode: String,
+    pub map: String,
+    dt_accumulator: f32,
+    pub outbox: Vec<(Recipient, ServerMessages)>,
+}
+
+const RESPAWN_DELAY: f32 = 2.0;
+
+const ITEM_RADIUS: f32 = 1.0;
+
+// characters
+const MAX_CHAT_LEN: usize = 200;
+// messages per second, and how many can be sent at once
+const CHAT_RATE: f32 = 1.0;
+const CHAT_BURST: f32 = 5.0;
+
+// spawn points closer than this to an enemy are avoided
+const SPAWN_SAFE_DISTANCE: f32 = 10.0;
+
+// how far outside the box of the target a shot may land,
+// the client sees everyone a little in the past
+const SHOT_TOLERANCE: f32 = 1.0;
+
+impl Game {
+    // the map is loaded by load_map, the mode has been checked by parse_game_mode
+    pub fn new(mode: &str, map: String, cvars: Cvars) -> Self {
+        let mut shared = Shared::new();
+        shared.cvars = cvars;
+
+        let game_mode = create_game_mode(mode)
+            .unwrap_or_else(|| panic!("unknown game mode: {}", mode));
+        let current_match = Match::new(game_mode, &shared.cvars);
+
+        Self {
+            shared,
+            client_ids: HashMap::new(),
+            time: 0.0,
+            current_tick: 0,
+            current_match,
+            navmesh: NavMesh::default(),
+            bots: Vec::new(),
+            chat_limits: HashMap::new(),
+            cmd_buffers: HashMap::new(),
+            mode: mode.to_string(),
+            map,
+            dt_accumulator: 0.0,
+            outbox: Vec::new(),
+        }
+    }
+
+    // one server tick, the messages of the clients are handled before it
+    pub async fn tick(&mut self, dt: f32) {
+        self.current_tick += 1;
+        self.time += dt;
+        self.dt_accumulator += dt;
+
+        self.update_bots(dt);
+        self.apply_cmds();
+        self.update_match(dt);
+
+        let step = self.shared.cvars.sv_physics_step;
+        while self.dt_accumulator >= step {
+            self.update_movers(step);
+            self.shared.handle_physics(step).await;
+            self.dt_accumulator -= step;
+        }
+
+        self.update_items(dt);
+    }
+
+    pub async fn load_map(&mut self) -> Result<(), String> {
+        self.shared = self.load_shared(&self.map).await?;
+        self.setup_map();
+        Ok(())
+    }
+
+    // the map goes into a fresh Shared, so a broken one leaves the running game alone
+    async fn load_shared(&self, map: &str) -> Result<Shared, String> {
+        let mut shared = Shared::new();
+        shared.cvars = self.shared.cvars.clone();
+        shared.load_map(map).await?;
+        Ok(shared)
+    }
+
+    fn setup_map(&mut self) {
+        self.spawn_items();
+
+        // the query pipeline only knows about the map after a step
+        self.shared.physics.step(self.shared.cvars.sv_physics_step, self.shared.cvars.sv_gravity);
+        self.navmesh = NavMesh::build(&self.shared.physics);
+        println!("{}: navmesh with {} nodes", self.map, self.navmesh.nodes.len());
+    }
+
+    // everything is thrown away, players and bots start over on the new map.
+    // the clients need the new world_messages, sending them is up to the caller
+    pub async fn change_map(&mut self, map: String) -> Result<(), String> {
+        if !Path::new(&map).is_file() {
+            return Err(format!("no such map: {}", map));
+        }
+        let shared = self.load_shared(&map).await?;
+
+        let bots: Vec<ClientId> = self.bots.drain(..).map(|bot| bot.client_id).collect();
+        let players: Vec<ClientId> = self.client_ids.keys()
+            .filter(|client| !bots.contains(client))
+            .copied()
+            .collect();
+
+        self.map = map;
+        self.shared = shared;
+        self.client_ids.clear();
+        self.current_match = Match::new(create_game_mode(&self.mode).unwrap(), &self.shared.cvars);
+        self.setup_map();
+
+        for client_id in bots {
+            self.spawn_bot(client_id);
+        }
+
+        for client_id in players {
+            let id = self.spawn_player();
+            self.client_ids.insert(client_id, id);
+        }
+
+        self.announce(format!("changed map to {}", self.map));
+        Ok(())
+    }
+
+    // the initial world is too large for a single message,
+    // so the server sends it in chunks
+    pub fn world_messages(&self) -> ServerMessages {
+        vec![
+            ServerMessage::Cvars(self.shared.cvars.clone()),
+            ServerMessage::PhysicsState(
+                self.shared.physics.state.rigid_body_set.clone(),
+                self.shared.physics.state.collider_set.clone()
+            ),
+            ServerMessage::Ecs(Columns {
+                MeshWrapper: clone_column!(self, &MeshWrapper),
+                Transform: clone_column!(self, &Transform),
+                Water: clone_column!(self, &Water),
+                ..Columns::default()
+            }),
+        ]
+    }
+
+    pub fn snapshot_messages(&self) -> ServerMessages {
+        vec![
+            ServerMessage::Ecs(Columns {
+                Player: clone_column!(self, &Player),
+                Score: clone_column!(self, &Score),
+                Team: clone_column!(self, &Team),
+                Flag: clone_column!(self, &Flag),
+                Item: clone_column!(self, &Item),
+                RigidBodyHandle: clone_column!(self, &RigidBodyHandle),
+                ColliderHandle: clone_column!(self, &ColliderHandle),
+                ModelId: clone_column!(self, &ModelId),
+                ..Columns::default()
+            }),
+            ServerMessage::PhysicsDiff(self.shared.physics.get_physics_diff()),
+            ServerMessage::Match(self.current_match.state.clone()),
+        ]
+    }
+
+    pub fn cvars(&self) -> String {
+        Cvars::LIST.iter()
+            .filter(|info| !has_flag(info.flags, SAVED))
+            .map(|info| format!("{:<16} {:<8} {}", info.name, self.shared.cvars.get(info.name).unwrap(), info.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        // the client ones are set in each client
+        if Cvars::info(name).is_some_and(|info| has_flag(info.flags, SAVED)) {
+            return Err(format!("{} is a client setting", name));
+        }
+
+        self.shared.cvars.set(name, value)?;
+
+        if name == "sv_score_limit" {
+            self.current_match.update_score_limit(&self.shared.cvars);
+        }
+        if has_flag(Cvars::info(name).unwrap().flags, REPLICATED) {
+            self.broadcast(vec![ServerMessage::Cvars(self.shared.cvars.clone())]);
+        }
+
+        Ok(())
+    }
+
+    // turns a spectator into a player
+    pub fn join(&mut self, client_id: ClientId) -> Entity {
+        let id = self.spawn_player();
+        self.client_ids.insert(client_id, id);
+        self.send(client_id, vec![ServerMessage::AssignId(id)]);
+        self.announce(format!("{} joined", player_name(id)));
+        id
+    }
+
+    pub fn disconnect(&mut self, client_id: ClientId) {
+        self.chat_limits.remove(&client_id);
+        self.cmd_buffers.remove(&client_id);
+        self.remove_player(client_id);
+    }
+
+    // removes the player of a client, the client itself may stay connected
+    pub fn remove_player(&mut self, client_id: ClientId) {
+        let Some(id) = self.client_ids.remove(&client_id) else { return };
+
+        if let Ok(handle) = self.shared.ecs.get::<&RigidBodyHandle>(id).map(|handle| *handle) {
+            self.shared.physics.remove_body(handle);
+        }
+        self.shared.ecs.despawn(id).unwrap();
+        self.broadcast(vec![ServerMessage::Despawn(id)]);
+        self.announce(format!("{} left", player_name(id)));
+    }
+
+    pub fn spawn_bot(&mut self, client_id: ClientId) {
+        let id = self.spawn_player();
+        self.client_ids.insert(client_id, id);
+        self.bots.push(Bot::new(client_id));
+    }
+
+    pub fn remove_bot(&mut self, client_id: ClientId) -> bool {
+        let Some(i) = self.bots.iter().position(|bot| bot.client_id == client_id) else { return false };
+        self.bots.remove(i);
+        self.remove_player(client_id);
+        true
+    }
+
+    pub fn is_bot(&self, client_id: ClientId) -> bool {
+        self.bots.iter().any(|bot| bot.client_id == client_id)
+    }
+
+    fn update_bots(&mut self, dt: f32) {
+        let mut msgs = Vec::new();
+        for bot in self.bots.iter_mut() {
+            let id = self.client_ids[&bot.client_id];
+            msgs.push((bot.client_id, bot.think(&self.shared, &self.navmesh, id, self.current_tick, self.time, dt)));
+        }
+
+        for (client_id, bot_msgs) in msgs {
+            for msg in bot_msgs {
+                self.handle_msg(client_id, msg);
+            }
+        }
+    }
+
+    // the buffered commands that are due, players without a new one keep the last
+    fn apply_cmds(&mut self) {
+        for (client_id, id) in self.client_ids.iter() {
+            let Some(cmd) = self.cmd_buffers.get_mut(client_id).and_then(|cmds| cmds.take(self.current_tick)) else { continue };
+
+            if let Ok(mut player) = self.shared.ecs.get::<&mut Player>(*id) && !player.dead() {
+                player.set_cmd(cmd);
+            }
+        }
+    }
+
+    fn spawn_items(&mut self) {
+        let points: Vec<(Entity, Vec3, ItemKind, Option<f32>)> = self.shared.ecs.query::<(&PointObject, &Properties)>().iter()
+            .filter_map(|(id, (pos, props))| props.item.map(|kind| (id, pos.0, kind, props.respawn)))
+            .collect();
+
+        for (id, pos, kind, respawn) in points {
+            let col = self.shared.physics.spawn_sensor(pos, ITEM_RADIUS);
+            self.shared.ecs.insert(id, (Item::new(kind, pos, respawn), col)).unwrap();
+        }
+    }
+
+    fn update_items(&mut self, dt: f32) {
+        let items: Vec<(Entity, ColliderHandle)> = self.shared.ecs.query::<(&Item, &ColliderHandle)>().iter()
+            .map(|(id, (_, col))| (id, *col))
+            .collect();
+
+        for (id, col) in items {
+            {
+                let mut item = self.shared.ecs.get::<&mut Item>(id).unwrap();
+                if !item.available() {
+                    item.timer -= dt;
+                    continue;
+                }
+            }
+
+            for body in self.shared.physics.sensor_bodies(col) {
+                let Some(owner) = self.shared.body_owner(body) else { continue };
+                let Ok(mut player) = self.shared.ecs.get::<&mut Player>(owner) else { continue };
+
+                let mut item = self.shared.ecs.get::<&mut Item>(id).unwrap();
+                if !player.dead() && item.kind.apply(&mut player) {
+                    item.timer = item.respawn;
+                    break;
+                }
+            }
+        }
+    }
+
+    // the bodies are moved by the next physics step, which also gives them the velocity
+    // that carries the players standing on them
+    fn update_movers(&mut self, dt: f32) {
+        let movers: Vec<(Entity, RigidBodyHandle, Option<ColliderHandle>)> = self.shared.ecs.query::<(&Mover, &RigidBodyHandle)>().iter()
+            .map(|(id, (mover, handle))| (id, *handle, mover.sensor))
+            .collect();
+
+        for (id, handle, sensor) in movers {
+            let triggered = sensor.is_some_and(|sensor| {
+                self.shared.physics.sensor_bodies(sensor).into_iter()
+                    .filter_map(|body| self.shared.body_owner(body))
+                    .any(|owner| self.shared.ecs.get::<&Player>(owner).is_ok_and(|player| !player.dead()))
+            });
+
+            let pos = self.shared.ecs.get::<&mut Mover>(id).unwrap().advance(dt, triggered);
+            self.shared.physics.get_rig_mut(handle).set_next_kinematic_translation(conv_vec_1(pos));
+        }
+    }
+
+    // picks a random spawn point that is not blocked and not close to enemies,
+    // falling back to the safest one if there is no such point.
+    // team spawn points are used for players on that team if the map has any
+    fn select_spawn(&self, player: Option<Entity>) -> Vec3 {
+        let exclude_body = player
+            .and_then(|id| self.shared.ecs.get::<&RigidBodyHandle>(id).ok())
+            .map(|handle| *handle);
+        let team = player
+            .and_then(|id| self.shared.ecs.get::<&Team>(id).ok())
+            .map(|team| *team);
+
+        let enemies: Vec<Vec3> = self.shared.ecs.query::<(&Player, &RigidBodyHandle)>().iter()
+            .filter(|(id, (other, _))| !other.dead() && match player {
+                Some(player) => *id != player && !same_team(&self.shared, *id, player),
+                None => true,
+            })
+            .map(|(_, (_, handle))| conv_vec_2(*self.shared.physics.get_rig(*handle).translation()))
+            .collect();
+
+        let points: Vec<(Vec3, Option<Team>)> = self.shared.ecs.query::<(&PointObject, &Properties)>().iter()
+            .filter(|(_, (_, props))| props.spawn == Some(true))
+            .map(|(_, (pos, props))| (pos.0, props.team))
+            .collect();
+
+        let team_points = team.is_some() && points.iter().any(|(_, point_team)| *point_team == team);
+        let mut spawn_points: Vec<(Vec3, f32)> = Vec::new();
+
+        for (pos, point_team) in points {
+            if point_team != if team_points { team } else { None } {
+                continue;
+            }
+
+            if self.shared.physics.is_blocked(pos, PLAYER_SIZE, exclude_body) {
+                continue;
+            }
+
+            let nearest_enemy = enemies.iter()
+                .map(|enemy| enemy.distance(pos))
+                .fold(f32::INFINITY, f32::min);
+            spawn_points.push((pos, nearest_enemy));
+        }
+
+        let safe: Vec<Vec3> = spawn_points.iter()
+            .filter(|(_, distance)| *distance >= SPAWN_SAFE_DISTANCE)
+            .map(|(pos, _)| *pos)
+            .collect();
+
+        if let Some(pos) = safe.choose() {
+            return *pos;
+        }
+
+        spawn_points.iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(pos, _)| *pos)
+            .unwrap_or(Vec3::ZERO)
+    }
+
+    fn spawn_player(&mut self) -> Entity {
+        let (rig_handle, col_handle) = self.shared.physics.spawn_cube(Vec3::ZERO, PLAYER_SIZE);
+        self.shared.physics.get_rig_mut(rig_handle).lock_rotations(true, false);
+
+        // friction is up to the movement, and players don't bounce
+        let col = self.shared.physics.get_col_mut(col_handle);
+        col.set_friction(0.0);
+        col.set_friction_combine_rule(CoefficientCombineRule::Min);
+        col.set_restitution(0.0);
+        col.set_restitution_combine_rule(CoefficientCombineRule::Min);
+        let id = self.shared.ecs.spawn((
+            Player::new(),
+            Score::default(),
+            ModelId::Player,
+            rig_handle,
+            col_handle,
+        ));
+
+        self.current_match.on_player_join(&mut self.shared, id);
+        self.respawn(id);
+        id
+    }
+
+    fn update_match(&mut self, dt: f32) {
+        match self.current_match.tick(&mut self.shared, dt) {
+            Some(MatchEvent::Started) => {
+                println!("match started");
+
+                for (_, score) in self.shared.ecs.query_mut::<&mut Score>() {
+                    *score = Score { ping: score.ping, ..Score::default() };
+                }
+
+                let players: Vec<Entity> = self.shared.ecs.query::<&Player>().iter().map(|(id, _)| id).collect();
+                for id in players {
+                    self.respawn(id);
+                }
+            }
+            Some(MatchEvent::Ended) => {
+                println!("match ended: {:?}", self.current_match.state.phase);
+            }
+            None => {}
+        }
+    }
+
+    fn kill(&mut self, id: Entity) {
+        let handle = {
+            let Ok((handle, player)) = self.shared.ecs.query_one_mut::<(&RigidBodyHandle, &mut Player)>(id) else { return };
+            player.die(self.time + RESPAWN_DELAY);
+            *handle
+        };
+
+        // the body stays where it died, but stops colliding until respawn
+        let body = self.shared.physics.get_rig_mut(handle);
+        let pos = conv_vec_2(*body.translation());
+        body.set_enabled(false);
+        self.set_colliders_enabled(handle, false);
+
+        self.spawn_gibs(pos);
+    }
+
+    fn respawn(&mut self, id: Entity) {
+        let pos = self.select_spawn(Some(id));
+
+        let Ok((handle, player)) = self.shared.ecs.query_one_mut::<(&RigidBodyHandle, &mut Player)>(id) else { return };
+        player.respawn();
+        let handle = *handle;
+
+        let body = self.shared.physics.get_rig_mut(handle);
+        body.set_enabled(true);
+        body.set_position(Isometry::translation(pos.x, pos.y, pos.z), true);
+        body.set_linvel(Vector::zeros(), true);
+        body.set_angvel(Vector::zeros(), true);
+        self.shared.physics.resize_box(handle, PLAYER_SIZE);
+        self.set_colliders_enabled(handle, true);
+    }
+
+    fn set_colliders_enabled(&mut self, handle: RigidBodyHandle, enabled: bool) {
+        let colliders = self.shared.physics.get_rig(handle).colliders().to_vec();
+        for col in colliders {
+            self.shared.physics.get_col_mut(col).set_enabled(enabled);
+        }
+    }
+
+    // the top quarter of the player box counts as the head
+    // the client says what it hit, so the point has to be on the target
+    // and nothing of the map can be in between
+    fn valid_shot(&self, id: Entity, target: Entity, point: Vec3) -> bool {
+        if id == target || !point.is_finite() {
+            return false;
+        }
+
+        let Ok(size) = self.shared.ecs.get::<&Player>(target).map(|player| player.size()) else { return false };
+        let Some(pos) = self.shared.body_position(target) else { return false };
+        if ((point - pos).abs() - size / 2.0).max_element() > SHOT_TOLERANCE {
+            return false;
+        }
+
+        let Some(eye) = self.shared.eye_position(id) else { return false };
+        let dir = (point - eye).normalize_or_zero();
+        match self.shared.ray_intersection(eye, dir, &[id, target]) {
+            Some((hit, owner)) if hit.distance(eye) < eye.distance(point) - SHOT_TOLERANCE => {
+                // players and gibs are somewhere else on the client, only the map blocks
+                !owner.is_none_or(|owner| self.shared.ecs.satisfies::<&Mover>(owner).unwrap_or(false))
+            }
+            _ => true,
+        }
+    }
+
+    fn is_headshot(&self, target: Entity, point: Vec3) -> bool {
+        let Ok(mut query) = self.shared.ecs.query_one::<(&RigidBodyHandle, &Player)>(target) else { return false };
+        let Some((handle, player)) = query.get() else { return false };
+        let pos = conv_vec_2(*self.shared.physics.get_rig(*handle).translation());
+        point.y > pos.y + player.size().y * 0.25
+    }
+
+    fn apply_damage(&mut self, event: DamageEvent) {
+        if !self.current_match.allow_damage(&self.shared, &event) {
+            return;
+        }
+
+        let killed = match self.shared.ecs.get::<&mut Player>(event.victim) {
+            Ok(mut player) if !player.dead() => {
+                player.hurt(event.damage(&self.shared.cvars));
+                player.hp() == 0
+            }
+            _ => false,
+        };
+
+        if !killed {
+            return;
+        }
+
+        self.kill(event.victim);
+
+        if let Ok(mut score) = self.shared.ecs.get::<&mut Score>(event.victim) {
+            score.deaths += 1;
+            if event.suicide() {
+                score.suicides += 1;
+                score.frags -= 1;
+            }
+        }
+
+        if !event.suicide() && let Ok(mut score) = self.shared.ecs.get::<&mut Score>(event.attacker) {
+            score.frags += 1;
+        }
+
+        self.current_match.on_kill(&mut self.shared, &event);
+
+        if event.suicide() {
+            self.announce(format!("{} killed themselves", player_name(event.victim)));
+        } else {
+            self.announce(format!("{} fragged {}", player_name(event.attacker), player_name(event.victim)));
+        }
+        self.broadcast(vec![ServerMessage::Kill(event)]);
+    }
+
+    fn spawn_gibs(&mut self, target: Vec3) {
+        for x in 0..2 {
+            for z in 0..2 {
+                let x = (x * 2 - 1) as f32;
+                let z = (z * 2 - 1) as f32;
+                self.shared.ecs.spawn({
+                    let (rig, col) = self.shared.physics.spawn_cube(
+                        target + vec3(x / 2.0, 0.0, z / 2.0),
+                        vec3(0.5, 0.5, 0.5)
+                    );
+                    self.shared.physics.get_rig_mut(rig).set_linvel(vector![x, 0.0, z], false);
+                    (rig, col, ModelId::random_gib())
+                });
+            }
+        }
+
+        self.shared.ecs.spawn({
+            let (rig, col) = self.shared.physics.spawn_cube(
+                target + vec3(0.0, 1.0, 0.0),
+                vec3(0.5, 0.5, 0.5)
+            );
+            self.shared.physics.get_rig_mut(rig).set_linvel(vector![0.0, 10.0, 0.0], false);
+            (rig, col, ModelId::random_gib())
+        });
+    }
+
+    pub fn handle_msg(&mut self, cli_id: ClientId, msg: ClientMessage) {
+        // spectators have no player, all they can do is join and chat
+        let Some(&id) = self.client_ids.get(&cli_id) else {
+            match msg {
+                ClientMessage::Join => {
+                    self.join(cli_id);
+                }
+                ClientMessage::Chat { text, team_only } => self.spectator_chat(cli_id, text, team_only),
+                _ => {}
+            }
+            return;
+        };
+
+        match msg {
+            ClientMessage::UserCmds(cmds) => {
+                let buffer = self.cmd_buffers.entry(cli_id).or_default();
+                for cmd in cmds {
+                    buffer.push(cmd, self.current_tick);
+                }
+            }
+            ClientMessage::Shot { target, point, weapon } => {
+                let fired = self.shared.ecs.get::<&mut Player>(id)
+                    .is_ok_and(|mut player| !player.dead() && player.use_ammo(weapon));
+                if !fired || !self.valid_shot(id, target, point) {
+                    return;
+                }
+
+                let headshot = self.is_headshot(target, point);
+                self.apply_damage(DamageEvent {
+                    attacker: id,
+                    victim: target,
+                    weapon,
+                    headshot,
+                });
+            },
+            ClientMessage::Respawn => {
+                if self.shared.ecs.get::<&Player>(id).is_ok_and(|player| player.can_respawn(self.time)) {
+                    self.respawn(id);
+                }
+            },
+            ClientMessage::Join => {}, // already playing
+            ClientMessage::Rcon { .. } | ClientMessage::Ping(_) => {}, // handled by the server
+            ClientMessage::Chat { text, team_only } => {
+                self.chat(cli_id, id, text, team_only);
+            },
+        }
+    }
+
+    // the text cleaned up, None if there is nothing left or the client is over the limit
+    fn chat_text(&mut self, client_id: ClientId, text: String) -> Option<String> {
+        let text: String = text.trim().chars().filter(|c| !c.is_control()).take(MAX_CHAT_LEN).collect();
+        if text.is_empty() {
+            return None;
+        }
+
+        let limit = self.chat_limits.entry(client_id).or_insert_with(|| RateLimit::new(CHAT_RATE, CHAT_BURST));
+        limit.allow(self.time).then_some(text)
+    }
+
+    fn chat(&mut self, client_id: ClientId, id: Entity, text: String, team_only: bool) {
+        let Some(text) = self.chat_text(client_id, text) else { return };
+
+        println!("{}{}: {}", player_name(id), if team_only { " (team)" } else { "" }, text);
+
+        if !team_only {
+            self.broadcast(vec![ServerMessage::Chat { from: Some(id), text }]);
+            return;
+        }
+
+        // without teams this only goes back to the sender
+        let clients: Vec<ClientId> = self.client_ids.iter()
+            .filter(|(client, other)| !self.is_bot(**client) && (**other == id || same_team(&self.shared, id, **other)))
+            .map(|(client, _)| *client)
+            .collect();
+
+        for client in clients {
+            self.send(client, vec![ServerMessage::Chat { from: Some(id), text: text.clone() }]);
+        }
+    }
+
+    // there is no player to name them by, and their team is the other spectators
+    fn spectator_chat(&mut self, client_id: ClientId, text: String, team_only: bool) {
+        let Some(text) = self.chat_text(client_id, text) else { return };
+
+        let text = format!("spectator {}: {}", client_id, text);
+        println!("{}{}", text, if team_only { " (spectators)" } else { "" });
+
+        let recipient = if team_only { Recipient::Spectators } else { Recipient::All };
+        self.outbox.push((recipient, vec![ServerMessage::Chat { from: None, text }]));
+    }
+
+    // server messages in the chat
+    pub fn announce(&mut self, text: String) {
+        println!("{}", text);
+        self.broadcast(vec![ServerMessage::Chat { from: None, text }]);
+    }
+
+    fn send(&mut self, client_id: ClientId, msgs: ServerMessages) {
+        self.outbox.push((Recipient::Client(client_id), msgs));
+    }
+
+    fn broadcast(&mut self, msgs: ServerMessages) {
+        self.outbox.push((Recipient::All, msgs));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::shared::*;
+use super::*;
+
+const FLAG_TOUCH_DISTANCE: f32 = 2.0;
+const FLAG_RETURN_TIME: f32 = 30.0;
+
+#[derive(Default)]
+pub struct CaptureTheFlag {
+    flags: Vec<Entity>,
+}
+
+impl CaptureTheFlag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn drop_flags_of(&self, shared: &mut Shared, carrier: Entity, pos: Vec3) {
+        for id in &self.flags {
+            let mut flag = shared.ecs.get::<&mut Flag>(*id).unwrap();
+            if flag.carrier == Some(carrier) {
+                flag.carrier = None;
+                flag.pos = pos;
+                flag.return_timer = FLAG_RETURN_TIME;
+            }
+        }
+    }
+}
+
+impl GameMode for CaptureTheFlag {
+    fn name(&self) -> &'static str {
+        "capture the flag"
+    }
+
+    fn default_score_limit(&self) -> u32 {
+        3
+    }
+
+    fn on_start(&mut self, shared: &mut Shared, _state: &mut MatchState) {
+        if self.flags.is_empty() {
+            let bases: Vec<(Team, Vec3)> = shared.ecs.query::<(&PointObject, &Properties)>().iter()
+                .filter_map(|(_, (pos, props))| props.flag.map(|team| (team, pos.0)))
+                .collect();
+
+            if bases.is_empty() {
+                eprintln!("ctf: the map has no flag bases");
+            }
+
+            for (team, home) in bases {
+                self.flags.push(shared.ecs.spawn((Flag::new(team, home),)));
+            }
+        }
+
+        for id in &self.flags {
+            shared.ecs.get::<&mut Flag>(*id).unwrap().reset();
+        }
+    }
+
+    fn on_player_join(&mut self, shared: &mut Shared, _state: &mut MatchState, id: Entity) {
+        assign_team(shared, id);
+    }
+
+    fn on_kill(&mut self, shared: &mut Shared, _state: &mut MatchState, event: &DamageEvent) {
+        if let Some(pos) = shared.body_position(event.victim) {
+            self.drop_flags_of(shared, event.victim, pos);
+        }
+    }
+
+    fn on_tick(&mut self, shared: &mut Shared, state: &mut MatchState, dt: f32) {
+        let players: Vec<(Entity, Team, Vec3)> = shared.ecs.query::<(&Player, &Team)>().iter()
+            .filter(|(_, (player, _))| !player.dead())
+            .filter_map(|(id, (_, team))| shared.body_position(id).map(|pos| (id, *team, pos)))
+            .collect();
+
+        for id in self.flags.clone() {
+            let mut flag = (*shared.ecs.get::<&Flag>(id).unwrap()).clone();
+
+            if let Some(carrier) = flag.carrier {
+                match players.iter().find(|(id, _, _)| *id == carrier) {
+                    Some((_, _, pos)) => flag.pos = *pos + vec3(0.0, PLAYER_SIZE.y, 0.0),
+                    // the carrier left or died without on_kill
+                    None => {
+                        flag.carrier = None;
+                        flag.return_timer = FLAG_RETURN_TIME;
+                    }
+                }
+            } else if !flag.at_home() {
+                flag.return_timer -= dt;
+                if flag.return_timer <= 0.0 {
+                    flag.reset();
+                }
+            }
+
+            for (player, team, pos) in &players {
+                if flag.carrier.is_some() || pos.distance(flag.pos) > FLAG_TOUCH_DISTANCE {
+                    continue;
+                }
+
+                if *team != flag.team {
+                    flag.carrier = Some(*player);
+                } else if !flag.at_home() {
+                    flag.reset();
+                }
+            }
+
+            *shared.ecs.get::<&mut Flag>(id).unwrap() = flag;
+        }
+
+        // a carrier touching their own flag at home captures
+        for id in self.flags.clone() {
+            let home_flag = (*shared.ecs.get::<&Flag>(id).unwrap()).clone();
+            if !home_flag.at_home() {
+                continue;
+            }
+
+            for other in self.flags.clone() {
+                let mut flag = shared.ecs.get::<&mut Flag>(other).unwrap();
+                let Some(carrier) = flag.carrier else { continue };
+                if flag.team == home_flag.team {
+                    continue;
+                }
+
+                let Some((_, team, pos)) = players.iter().find(|(id, _, _)| *id == carrier) else { continue };
+                if *team == home_flag.team && pos.distance(home_flag.home) <= FLAG_TOUCH_DISTANCE {
+                    state.team_scores[team.index()] += 1;
+                    flag.reset();
+                }
+            }
+        }
+    }
+
+    fn check_end(&self, _shared: &Shared, state: &MatchState) -> bool {
+        state.score_limit > 0 && state.team_scores.iter().any(|score| *score >= state.score_limit as i32)
+    }
+
+    fn winner(&self, _shared: &Shared, state: &MatchState) -> Option<Winner> {
+        team_winner(state)
+    }
+
+    fn allow_damage(&self, shared: &Shared, event: &DamageEvent) -> bool {
+        event.suicide() || !same_team(shared, event.attacker, event.victim)
+    }
+}
@@ -0,0 +1,34 @@
+use crate::shared::*;
+use super::*;
+
+// free for all
+
+pub struct Deathmatch;
+
+impl GameMode for Deathmatch {
+    fn name(&self) -> &'static str {
+        "deathmatch"
+    }
+
+    fn default_score_limit(&self) -> u32 {
+        20
+    }
+
+    fn check_end(&self, shared: &Shared, state: &MatchState) -> bool {
+        state.score_limit > 0 && shared.ecs.query::<&Score>().iter()
+            .any(|(_, score)| score.frags >= state.score_limit as i32)
+    }
+
+    fn winner(&self, shared: &Shared, _state: &MatchState) -> Option<Winner> {
+        let mut scores: Vec<(Entity, i32)> = shared.ecs.query::<&Score>().iter()
+            .map(|(id, score)| (id, score.frags))
+            .collect();
+        scores.sort_by_key(|(_, frags)| std::cmp::Reverse(*frags));
+
+        match scores.as_slice() {
+            [(id, _)] => Some(Winner::Player(*id)),
+            [(id, first), (_, second), ..] if first > second => Some(Winner::Player(*id)),
+            _ => None,
+        }
+    }
+}
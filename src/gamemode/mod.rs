@@ -0,0 +1,172 @@
+use crate::shared::*;
+
+mod deathmatch;
+pub use deathmatch::*;
+
+mod team_deathmatch;
+pub use team_deathmatch::*;
+
+mod ctf;
+pub use ctf::*;
+
+// the rules of a match, the phases (warmup, playing, intermission)
+// and the time limit are handled by Match, the lengths come from the sv_ cvars
+
+pub trait GameMode {
+    fn name(&self) -> &'static str;
+
+    fn default_score_limit(&self) -> u32;
+
+    fn on_start(&mut self, _shared: &mut Shared, _state: &mut MatchState) {}
+
+    fn on_player_join(&mut self, _shared: &mut Shared, _state: &mut MatchState, _id: Entity) {}
+
+    fn on_kill(&mut self, _shared: &mut Shared, _state: &mut MatchState, _event: &DamageEvent) {}
+
+    fn on_tick(&mut self, _shared: &mut Shared, _state: &mut MatchState, _dt: f32) {}
+
+    // has the score limit been reached?
+    fn check_end(&self, shared: &Shared, state: &MatchState) -> bool;
+
+    // who is winning right now, None if it is a draw
+    fn winner(&self, shared: &Shared, state: &MatchState) -> Option<Winner>;
+
+    fn allow_damage(&self, _shared: &Shared, _event: &DamageEvent) -> bool {
+        true
+    }
+}
+
+pub fn create_game_mode(name: &str) -> Option<Box<dyn GameMode>> {
+    match name {
+        "dm" | "deathmatch" => Some(Box::new(Deathmatch)),
+        "tdm" | "teamdeathmatch" => Some(Box::new(TeamDeathmatch)),
+        "ctf" => Some(Box::new(CaptureTheFlag::new())),
+        _ => None,
+    }
+}
+
+// for the command line, so a typo is an error before anything starts
+pub fn parse_game_mode(name: &str) -> Result<String, String> {
+    create_game_mode(name)
+        .map(|_| name.to_string())
+        .ok_or_else(|| "expected dm, tdm or ctf".to_string())
+}
+
+pub enum MatchEvent {
+    Started,
+    Ended,
+}
+
+pub struct Match {
+    pub state: MatchState,
+    mode: Box<dyn GameMode>,
+}
+
+impl Match {
+    pub fn new(mode: Box<dyn GameMode>, cvars: &Cvars) -> Self {
+        Self {
+            state: MatchState {
+                mode: mode.name().to_string(),
+                phase: MatchPhase::Warmup,
+                time_remaining: cvars.sv_warmup,
+                score_limit: score_limit(mode.as_ref(), cvars),
+                team_scores: [0, 0],
+            },
+            mode,
+        }
+    }
+
+    // called when sv_score_limit changes mid match
+    pub fn update_score_limit(&mut self, cvars: &Cvars) {
+        self.state.score_limit = score_limit(self.mode.as_ref(), cvars);
+    }
+
+    pub fn tick(&mut self, shared: &mut Shared, dt: f32) -> Option<MatchEvent> {
+        let Cvars { sv_warmup, sv_time_limit, sv_intermission, .. } = shared.cvars;
+        let limited = self.state.time_remaining > 0.0;
+        self.state.time_remaining = (self.state.time_remaining - dt).max(0.0);
+        let time_up = limited && self.state.time_remaining == 0.0;
+
+        match self.state.phase {
+            MatchPhase::Warmup => {
+                if time_up || sv_warmup <= 0.0 {
+                    self.state.phase = MatchPhase::Playing;
+                    self.state.time_remaining = sv_time_limit;
+                    self.state.team_scores = [0, 0];
+                    self.mode.on_start(shared, &mut self.state);
+                    return Some(MatchEvent::Started);
+                }
+            }
+            MatchPhase::Playing => {
+                self.mode.on_tick(shared, &mut self.state, dt);
+
+                if time_up || self.mode.check_end(shared, &self.state) {
+                    let winner = self.mode.winner(shared, &self.state);
+                    self.state.phase = MatchPhase::Intermission { winner };
+                    self.state.time_remaining = sv_intermission;
+                    return Some(MatchEvent::Ended);
+                }
+            }
+            MatchPhase::Intermission { .. } => {
+                if time_up || sv_intermission <= 0.0 {
+                    self.state.phase = MatchPhase::Warmup;
+                    self.state.time_remaining = sv_warmup;
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn on_player_join(&mut self, shared: &mut Shared, id: Entity) {
+        self.mode.on_player_join(shared, &mut self.state, id);
+    }
+
+    pub fn on_kill(&mut self, shared: &mut Shared, event: &DamageEvent) {
+        // only the playing phase counts
+        if self.state.phase == MatchPhase::Playing {
+            self.mode.on_kill(shared, &mut self.state, event);
+        }
+    }
+
+    pub fn allow_damage(&self, shared: &Shared, event: &DamageEvent) -> bool {
+        !matches!(self.state.phase, MatchPhase::Intermission { .. })
+            && self.mode.allow_damage(shared, event)
+    }
+}
+
+// -1 uses the default of the mode
+fn score_limit(mode: &dyn GameMode, cvars: &Cvars) -> u32 {
+    u32::try_from(cvars.sv_score_limit).unwrap_or(mode.default_score_limit())
+}
+
+// helpers shared by the team modes
+
+pub fn assign_team(shared: &mut Shared, id: Entity) -> Team {
+    let mut counts = [0; 2];
+    for (_, (team, _)) in shared.ecs.query::<(&Team, &Player)>().iter() {
+        counts[team.index()] += 1;
+    }
+
+    let team = if counts[Team::Blue.index()] < counts[Team::Red.index()] { Team::Blue } else { Team::Red };
+    shared.ecs.insert_one(id, team).unwrap();
+    team
+}
+
+pub fn same_team(shared: &Shared, a: Entity, b: Entity) -> bool {
+    match (shared.ecs.get::<&Team>(a), shared.ecs.get::<&Team>(b)) {
+        (Ok(a), Ok(b)) => *a == *b,
+        _ => false,
+    }
+}
+
+pub fn team_winner(state: &MatchState) -> Option<Winner> {
+    let [red, blue] = state.team_scores;
+    if red > blue {
+        Some(Winner::Team(Team::Red))
+    } else if blue > red {
+        Some(Winner::Team(Team::Blue))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,41 @@
+use crate::shared::*;
+use super::*;
+
+pub struct TeamDeathmatch;
+
+impl GameMode for TeamDeathmatch {
+    fn name(&self) -> &'static str {
+        "team deathmatch"
+    }
+
+    fn default_score_limit(&self) -> u32 {
+        50
+    }
+
+    fn on_player_join(&mut self, shared: &mut Shared, _state: &mut MatchState, id: Entity) {
+        assign_team(shared, id);
+    }
+
+    fn on_kill(&mut self, shared: &mut Shared, state: &mut MatchState, event: &DamageEvent) {
+        if event.suicide() {
+            if let Ok(team) = shared.ecs.get::<&Team>(event.victim) {
+                state.team_scores[team.index()] -= 1;
+            }
+        } else if let Ok(team) = shared.ecs.get::<&Team>(event.attacker) {
+            state.team_scores[team.index()] += 1;
+        }
+    }
+
+    fn check_end(&self, _shared: &Shared, state: &MatchState) -> bool {
+        state.score_limit > 0 && state.team_scores.iter().any(|score| *score >= state.score_limit as i32)
+    }
+
+    fn winner(&self, _shared: &Shared, state: &MatchState) -> Option<Winner> {
+        team_winner(state)
+    }
+
+    // no friendly fire
+    fn allow_damage(&self, shared: &Shared, event: &DamageEvent) -> bool {
+        event.suicide() || !same_team(shared, event.attacker, event.victim)
+    }
+}
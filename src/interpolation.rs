@@ -0,0 +1,44 @@
+use crate::shared::*;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// remote bodies are rendered this far in the past,
+// so there is (almost) always a snapshot on both sides
+pub const INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+
+const MAX_SNAPSHOTS: usize = 32;
+
+// positions by the server time of their snapshot
+#[derive(Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(f64, Isometry<f32>)>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, time: f64, pos: Isometry<f32>) {
+        self.snapshots.push_back((time, pos));
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn sample(&mut self, time: f64) -> Option<Isometry<f32>> {
+        // keep exactly one snapshot older than the sampled time
+        while self.snapshots.len() >= 2 && self.snapshots[1].0 <= time {
+            self.snapshots.pop_front();
+        }
+
+        let (from_time, from) = self.snapshots.front()?;
+        let Some((to_time, to)) = self.snapshots.get(1) else {
+            return Some(*from);
+        };
+
+        if time <= *from_time {
+            return Some(*from);
+        }
+
+        let t = (time - *from_time) / (*to_time - *from_time).max(f64::EPSILON);
+        Some(from.lerp_slerp(to, t.min(1.0) as f32))
+    }
+}
@@ -0,0 +1,18 @@
+pub mod shared;
+pub mod physics;
+pub mod movement;
+pub mod network;
+pub mod utils;
+pub mod components;
+pub mod cvars;
+pub mod demo;
+pub mod gamemode;
+pub mod navmesh;
+pub mod bot;
+pub mod console;
+pub mod game;
+pub mod interpolation;
+pub mod clock;
+pub mod server;
+pub mod client;
+use crate::shared::*;
@@ -0,0 +1,193 @@
+use crate::shared::*;
+
+// quake style movement: friction and acceleration on the ground, a capped wish speed
+// in the air that still lets strafing gain speed, buffered jumps, stepping up ledges,
+// crouching, swimming and riding movers.
+// nothing but the body, the command and the cvars goes in, so the prediction
+// of the client ends up where the server does
+
+// the floor is ignored by the step check below this height
+const STEP_SKIN: f32 = 0.05;
+
+// going up faster than this is a jump, not standing on the ground
+const MAX_GROUND_RISE: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveMode {
+    Ground,
+    Air,
+    Swim,
+}
+
+pub fn move_player(physics: &mut Physics, handle: RigidBodyHandle, player: &mut Player, waters: &[Water], cvars: &Cvars, dt: f32) {
+    let body = physics.get_rig(handle);
+    let mut pos = conv_vec_2(*body.translation());
+    let mut vel = conv_vec_2(*body.linvel());
+
+    player.swimming = in_water(waters, pos);
+
+    let ground = if vel.y <= MAX_GROUND_RISE { physics.ground(pos, player.size().y, handle) } else { None };
+    let mut on_ground = ground.is_some();
+
+    // a mover takes whoever stands on it along, the velocity of the player stays its own
+    if let Some(mover_vel) = ground.and_then(|col| physics.mover_velocity(col)) {
+        pos += mover_vel * dt;
+        physics.get_rig_mut(handle).set_translation(conv_vec_1(pos), true);
+    }
+
+    // crouching doesn't make sense while swimming, the button dives instead
+    let crouch = player.cmd.pressed(BUTTON_CROUCH) && !player.swimming;
+    if crouch != player.crouched && let Some(new_pos) = change_crouch(physics, handle, pos, crouch, on_ground) {
+        player.crouched = crouch;
+        pos = new_pos;
+        physics.resize_box(handle, player.size());
+        physics.get_rig_mut(handle).set_translation(conv_vec_1(pos), true);
+    }
+
+    let jump = player.buffered_jump(dt, cvars.sv_jump_buffer)
+        || (cvars.sv_auto_hop && player.cmd.pressed(BUTTON_JUMP));
+    if jump && on_ground && !player.swimming {
+        player.clear_jump();
+        vel.y = cvars.jump_speed();
+        // no friction on the tick of the jump, that's what makes bunny hopping keep its speed
+        on_ground = false;
+    }
+
+    let mode = if player.swimming {
+        MoveMode::Swim
+    } else if on_ground {
+        MoveMode::Ground
+    } else {
+        MoveMode::Air
+    };
+    vel = player_velocity(vel, &player.cmd, mode, player.crouched, cvars, dt);
+
+    if on_ground && let Some(y) = step_up(physics, handle, pos, vel, player.size(), cvars.sv_step_height, dt) {
+        pos.y = y;
+        physics.get_rig_mut(handle).set_translation(conv_vec_1(pos), true);
+    }
+
+    physics.get_rig_mut(handle).set_linvel(conv_vec_1(vel), true);
+}
+
+// the new velocity after one step, gravity and collisions are up to the physics
+pub fn player_velocity(mut vel: Vec3, cmd: &UserCmd, mode: MoveMode, crouched: bool, cvars: &Cvars, dt: f32) -> Vec3 {
+    if mode == MoveMode::Swim {
+        return swim_velocity(vel, cmd, cvars, dt);
+    }
+
+    let forward = cmd.flat_forward();
+    let right = vec3(-forward.z, 0.0, forward.x);
+
+    let speed = if crouched { cvars.sv_crouch_speed } else { cvars.sv_move_speed };
+
+    // diagonals are as fast as straight
+    let wish = (forward * cmd.forward + right * cmd.side).clamp_length_max(1.0);
+    let wish_speed = wish.length() * speed;
+    let wish_dir = wish.normalize_or_zero();
+
+    if mode == MoveMode::Ground {
+        apply_friction(&mut vel, cvars, dt);
+        accelerate(&mut vel, wish_dir, wish_speed, wish_speed, cvars.sv_accelerate, dt);
+    } else {
+        // only the speed towards wish_dir is capped, turning into it keeps adding more
+        accelerate(&mut vel, wish_dir, wish_speed, wish_speed.min(cvars.sv_air_speed), cvars.sv_air_accelerate, dt);
+    }
+
+    vel
+}
+
+// swimming goes where the view points, jump swims up and crouch dives
+fn swim_velocity(mut vel: Vec3, cmd: &UserCmd, cvars: &Cvars, dt: f32) -> Vec3 {
+    let forward = vec3(cmd.yaw.cos() * cmd.pitch.cos(), cmd.pitch.sin(), cmd.yaw.sin() * cmd.pitch.cos());
+    let right = vec3(-cmd.yaw.sin(), 0.0, cmd.yaw.cos());
+
+    let mut up = 0.0;
+    if cmd.pressed(BUTTON_JUMP) { up += 1.0; }
+    if cmd.pressed(BUTTON_CROUCH) { up -= 1.0; }
+
+    let wish = (forward * cmd.forward + right * cmd.side + Vec3::Y * up).clamp_length_max(1.0);
+    let wish_speed = wish.length() * cvars.sv_swim_speed;
+
+    // water slows down every direction, and without the stop speed of the ground
+    let speed = vel.length();
+    if speed > 0.0 {
+        vel *= (speed - speed * cvars.sv_water_friction * dt).max(0.0) / speed;
+    }
+
+    accelerate(&mut vel, wish.normalize_or_zero(), wish_speed, wish_speed, cvars.sv_accelerate, dt);
+
+    // the physics still applies the full gravity
+    vel.y += cvars.sv_gravity * cvars.sv_buoyancy * dt;
+
+    vel
+}
+
+// where the body goes when crouching or standing up, None if there is no room to stand.
+// on the ground the feet stay where they are, in the air the body shrinks around its center
+fn change_crouch(physics: &Physics, handle: RigidBodyHandle, pos: Vec3, crouch: bool, on_ground: bool) -> Option<Vec3> {
+    let shift = if on_ground { (PLAYER_SIZE.y - CROUCH_SIZE.y) / 2.0 } else { 0.0 };
+
+    if crouch {
+        return Some(pos - vec3(0.0, shift, 0.0));
+    }
+
+    // the ceiling check, a bit smaller so touching the floor or a wall doesn't count
+    let standing = pos + vec3(0.0, shift, 0.0);
+    let size = PLAYER_SIZE - Vec3::splat(STEP_SKIN);
+    if physics.overlaps(standing, size, handle) {
+        return None;
+    }
+
+    Some(standing)
+}
+
+fn apply_friction(vel: &mut Vec3, cvars: &Cvars, dt: f32) {
+    let speed = vel.xz().length();
+    if speed < 0.01 {
+        vel.x = 0.0;
+        vel.z = 0.0;
+        return;
+    }
+
+    // slow ones stop quickly instead of sliding forever
+    let drop = speed.max(cvars.sv_stop_speed) * cvars.sv_friction * dt;
+    let scale = (speed - drop).max(0.0) / speed;
+    vel.x *= scale;
+    vel.z *= scale;
+}
+
+fn accelerate(vel: &mut Vec3, wish_dir: Vec3, wish_speed: f32, max_speed: f32, accel: f32, dt: f32) {
+    let add = max_speed - vel.dot(wish_dir);
+    if add <= 0.0 {
+        return;
+    }
+
+    *vel += wish_dir * (accel * wish_speed * dt).min(add);
+}
+
+// walking into a ledge no higher than step_height puts the player on top of it,
+// returns the new height of the body
+fn step_up(physics: &Physics, handle: RigidBodyHandle, pos: Vec3, vel: Vec3, size: Vec3, step_height: f32, dt: f32) -> Option<f32> {
+    let motion = vec3(vel.x, 0.0, vel.z) * dt;
+    if motion.length() < 0.001 || step_height <= 0.0 {
+        return None;
+    }
+
+    // a little further than this step goes, so the ledge is found before the body stops
+    let ahead = motion + motion.normalize() * STEP_SKIN;
+    let body = vec3(size.x, size.y - STEP_SKIN, size.z);
+    let feet = vec3(0.0, STEP_SKIN / 2.0, 0.0);
+
+    // nothing in the way, or a wall that is too high
+    if !physics.overlaps(pos + feet + ahead, body, handle) {
+        return None;
+    }
+    let up = pos + vec3(0.0, step_height, 0.0);
+    if physics.overlaps(up, size, handle) || physics.overlaps(up + ahead, size, handle) {
+        return None;
+    }
+
+    let drop = physics.drop_distance(up + ahead, size, step_height, handle)?;
+    Some(up.y - drop + 0.01)
+}
@@ -0,0 +1,240 @@
+use crate::shared::*;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+// a walkable grid sampled from the map trimesh,
+// a cell can have several nodes on top of each other (under and on a platform)
+
+const CELL_SIZE: f32 = 1.0;
+const MAX_SLOPE: f32 = 0.7; // min y of the normal of a walkable triangle
+pub const MAX_STEP: f32 = 0.5; // walk up without jumping
+const MAX_JUMP: f32 = 1.2;
+const MAX_DROP: f32 = 4.0;
+const SAME_NODE: f32 = 0.5; // max height difference of merged samples
+
+pub struct NavNode {
+    pub pos: Vec3, // where the center of a standing player would be
+    edges: Vec<(usize, f32)>,
+}
+
+#[derive(Default)]
+pub struct NavMesh {
+    pub nodes: Vec<NavNode>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl NavMesh {
+    // the colliders must already be in the query pipeline (after a physics step)
+    pub fn build(physics: &Physics) -> Self {
+        let mut navmesh = Self {
+            nodes: Vec::new(),
+            cells: HashMap::new(),
+        };
+
+        for (_, col) in physics.state.collider_set.iter() {
+            if col.parent().is_some() {
+                continue;
+            }
+            let Some(trimesh) = col.shape().as_trimesh() else { continue };
+
+            for tri in trimesh.triangles() {
+                let [a, b, c] = [tri.a, tri.b, tri.c].map(|p| conv_vec_2((col.position() * p).coords));
+                let normal = (b - a).cross(c - a).normalize_or_zero();
+                if normal.y < MAX_SLOPE {
+                    continue;
+                }
+
+                navmesh.rasterize(physics, a, b, c);
+            }
+        }
+
+        navmesh.prune_edges();
+        navmesh.connect();
+        navmesh
+    }
+
+    // nodes at the edge of a floor are dropped, so paths keep away from ledges
+    fn prune_edges(&mut self) {
+        let keep: Vec<bool> = self.nodes.iter().map(|node| {
+            let (x, z) = Self::cell(node.pos);
+            [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().all(|(dx, dz)| {
+                self.cells.get(&(x + dx, z + dz)).is_some_and(|cell| {
+                    cell.iter().any(|i| (self.nodes[*i].pos.y - node.pos.y).abs() <= MAX_STEP)
+                })
+            })
+        }).collect();
+
+        let nodes = std::mem::take(&mut self.nodes);
+        self.cells.clear();
+        for (node, keep) in nodes.into_iter().zip(keep) {
+            if keep {
+                self.cells.entry(Self::cell(node.pos)).or_default().push(self.nodes.len());
+                self.nodes.push(node);
+            }
+        }
+    }
+
+    fn cell(pos: Vec3) -> (i32, i32) {
+        ((pos.x / CELL_SIZE).floor() as i32, (pos.z / CELL_SIZE).floor() as i32)
+    }
+
+    // adds a node for every cell center covered by the triangle
+    fn rasterize(&mut self, physics: &Physics, a: Vec3, b: Vec3, c: Vec3) {
+        let min = a.min(b).min(c);
+        let max = a.max(b).max(c);
+        let (min_x, min_z) = Self::cell(min);
+        let (max_x, max_z) = Self::cell(max);
+
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                let point = vec2((x as f32 + 0.5) * CELL_SIZE, (z as f32 + 0.5) * CELL_SIZE);
+                let Some(y) = triangle_height(point, a, b, c) else { continue };
+                let pos = vec3(point.x, y + PLAYER_SIZE.y / 2.0, point.y);
+
+                let cell = self.cells.entry((x, z)).or_default();
+                if cell.iter().any(|i| (self.nodes[*i].pos.y - pos.y).abs() < SAME_NODE) {
+                    continue;
+                }
+
+                if blocked_by_map(physics, pos) {
+                    continue;
+                }
+
+                cell.push(self.nodes.len());
+                self.nodes.push(NavNode { pos, edges: Vec::new() });
+            }
+        }
+    }
+
+    fn connect(&mut self) {
+        for i in 0..self.nodes.len() {
+            let pos = self.nodes[i].pos;
+            let (x, z) = Self::cell(pos);
+
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    if dx == 0 && dz == 0 {
+                        continue;
+                    }
+                    let Some(cell) = self.cells.get(&(x + dx, z + dz)) else { continue };
+
+                    for j in cell.clone() {
+                        let other = self.nodes[j].pos;
+                        let rise = other.y - pos.y;
+                        if (-MAX_DROP..=MAX_JUMP).contains(&rise) {
+                            self.nodes[i].edges.push((j, pos.distance(other)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn nearest(&self, pos: Vec3) -> Option<usize> {
+        self.nodes.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.pos.distance_squared(pos).total_cmp(&b.pos.distance_squared(pos)))
+            .map(|(i, _)| i)
+    }
+
+    // A*, returns the waypoints after the start
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.nearest(from)?;
+        let goal = self.nearest(to)?;
+
+        let mut open = BinaryHeap::new();
+        let mut costs = vec![f32::INFINITY; self.nodes.len()];
+        let mut came_from = vec![usize::MAX; self.nodes.len()];
+
+        costs[start] = 0.0;
+        open.push(Candidate { node: start, estimate: 0.0 });
+
+        while let Some(Candidate { node, .. }) = open.pop() {
+            if node == goal {
+                let mut path = vec![self.nodes[goal].pos];
+                let mut node = goal;
+                while came_from[node] != start && came_from[node] != usize::MAX {
+                    node = came_from[node];
+                    path.push(self.nodes[node].pos);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for (next, distance) in &self.nodes[node].edges {
+                let cost = costs[node] + distance;
+                if cost < costs[*next] {
+                    costs[*next] = cost;
+                    came_from[*next] = node;
+                    open.push(Candidate {
+                        node: *next,
+                        estimate: cost + self.nodes[*next].pos.distance(self.nodes[goal].pos),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn random_node(&self) -> Option<Vec3> {
+        self.nodes.choose().map(|node| node.pos)
+    }
+}
+
+// for the open list, lowest estimate first
+struct Candidate {
+    node: usize,
+    estimate: f32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// height of the triangle at point (x, z), None if it is outside
+fn triangle_height(point: Vec2, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let (e1, e2, d) = (b.xz() - a.xz(), c.xz() - a.xz(), point - a.xz());
+    let det = e1.perp_dot(e2);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let u = d.perp_dot(e2) / det;
+    let v = e1.perp_dot(d) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    Some(a.y + (b.y - a.y) * u + (c.y - a.y) * v)
+}
+
+// does the map leave room for a player standing at pos?
+fn blocked_by_map(physics: &Physics, pos: Vec3) -> bool {
+    // a bit smaller, so touching the floor and walls doesn't count
+    let size = (PLAYER_SIZE - vec3(0.2, 0.2, 0.2)) / 2.0;
+    let shape = Cuboid::new(vector![size.x, size.y, size.z]);
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+
+    physics.query_pipeline(filter)
+        .intersect_shape(Isometry::translation(pos.x, pos.y + 0.1, pos.z), &shape)
+        .next()
+        .is_some()
+}
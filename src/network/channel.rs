@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use renet::{ChannelConfig, ConnectionConfig, SendType};
+
+// the same layout is used in both directions,
+// the order of Channel::ALL is also the send priority
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Input,
+    Events,
+    Snapshot,
+    Bulk,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Channel::Input,
+        Channel::Events,
+        Channel::Snapshot,
+        Channel::Bulk,
+    ];
+
+    pub fn config(self) -> ChannelConfig {
+        let (max_memory_usage_bytes, send_type) = match self {
+            // small and frequent, a lost command is sent again with the next ones
+            Channel::Input => (256 * 1024, SendType::Unreliable),
+            Channel::Events => (1024 * 1024, SendType::ReliableOrdered {
+                resend_time: Duration::from_millis(150),
+            }),
+            // a lost snapshot is replaced by the next one
+            Channel::Snapshot => (8 * 1024 * 1024, SendType::Unreliable),
+            // chunks are put back in order by the receiver
+            Channel::Bulk => (32 * 1024 * 1024, SendType::ReliableUnordered {
+                resend_time: Duration::from_millis(300),
+            }),
+        };
+
+        ChannelConfig {
+            channel_id: self.into(),
+            max_memory_usage_bytes,
+            send_type,
+        }
+    }
+}
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> u8 {
+        channel as u8
+    }
+}
+
+pub fn connection_config() -> ConnectionConfig {
+    let channels: Vec<_> = Channel::ALL.iter().map(|channel| channel.config()).collect();
+
+    ConnectionConfig {
+        server_channels_config: channels.clone(),
+        client_channels_config: channels,
+        ..ConnectionConfig::default()
+    }
+}
+
+// messages are batched per channel before being sent
+
+pub trait ChannelMessage {
+    fn channel(&self) -> Channel;
+}
+
+pub fn split_by_channel<T: ChannelMessage>(msgs: Vec<T>) -> Vec<(Channel, Vec<T>)> {
+    let mut batches: Vec<(Channel, Vec<T>)> = Vec::new();
+
+    for msg in msgs {
+        let channel = msg.channel();
+        match batches.iter_mut().find(|(c, _)| *c == channel) {
+            Some((_, batch)) => batch.push(msg),
+            None => batches.push((channel, vec![msg])),
+        }
+    }
+
+    batches
+}
@@ -0,0 +1,79 @@
+use crate::*;
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+// an in-memory Socket between a client and a server in the same process,
+// both ends can live on different threads
+
+// where the packets of the local client come from, as seen by the server.
+// port 0 can't be the source of a real udp packet
+pub const LOOPBACK_ADDR: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+pub struct LoopbackSocket {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    peer: SocketAddr, // the address received packets come from
+}
+
+// the client end and the server end, server_addr has to be the address the client connects to
+pub fn loopback(server_addr: SocketAddr) -> (LoopbackSocket, LoopbackSocket) {
+    let (to_server, from_client) = channel();
+    let (to_client, from_server) = channel();
+
+    let client = LoopbackSocket { sender: to_server, receiver: from_server, peer: server_addr };
+    let server = LoopbackSocket { sender: to_client, receiver: from_client, peer: LOOPBACK_ADDR };
+    (client, server)
+}
+
+impl Socket for LoopbackSocket {
+    fn send_to(&mut self, buf: &[u8], _addr: SocketAddr) -> io::Result<usize> {
+        // the other end is gone, same as a packet nobody listens to
+        let _ = self.sender.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        match self.receiver.try_recv() {
+            Ok(data) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok((len, self.peer))
+            }
+            Err(_) => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+// the server socket of a listen server, the local client goes through
+// the loopback and everyone else through the network
+pub struct ListenSocket {
+    local: LoopbackSocket,
+    remote: Box<dyn Socket>,
+}
+
+impl ListenSocket {
+    pub fn new(local: LoopbackSocket, remote: Box<dyn Socket>) -> Self {
+        Self { local, remote }
+    }
+}
+
+impl Socket for ListenSocket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        if addr == LOOPBACK_ADDR {
+            self.local.send_to(buf, addr)
+        } else {
+            self.remote.send_to(buf, addr)
+        }
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        match self.local.recv_from(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => self.remote.recv_from(buf),
+            result => result,
+        }
+    }
+}
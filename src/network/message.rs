@@ -3,19 +3,91 @@ use crate::*;
 pub type ServerMessages = Vec<ServerMessage>;
 pub type ClientMessages = Vec<ClientMessage>;
 
+// the world messages are large, but only sent once
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ServerMessage {
     AssignId(Entity),
     Ecs(Columns),
     PhysicsState(RigidBodySet, ColliderSet),
     PhysicsDiff(PhysicsDiff),
+    Chunk(Chunk),
+    Kill(DamageEvent),
+    Despawn(Entity),
+    Match(MatchState),
+    Chat { from: Option<Entity>, text: String }, // None is the server
+    ChangeMap(String),
+    RconOutput(String),
+    Cvars(Cvars),
+    Tick(Tick), // first in every batch, the server tick it was sent at
+    Pong(f64), // the time of the ping
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientMessage {
-    SetMoveState(MoveState),
-    SetYaw(f32),
-    Shot(Entity),
+    UserCmds(Vec<UserCmd>), // the newest last, with the previous ones again
+    Shot { target: Entity, point: Vec3, weapon: Weapon },
+    Respawn,
+    Join,
+    Chat { text: String, team_only: bool },
+    Rcon { password: String, command: String },
+    Ping(f64), // the local time of the client, sent back in the pong
+}
+
+impl ServerMessage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerMessage::AssignId(_) => "AssignId",
+            ServerMessage::Ecs(_) => "Ecs",
+            ServerMessage::PhysicsState(..) => "PhysicsState",
+            ServerMessage::PhysicsDiff(_) => "PhysicsDiff",
+            ServerMessage::Chunk(_) => "Chunk",
+            ServerMessage::Kill(_) => "Kill",
+            ServerMessage::Despawn(_) => "Despawn",
+            ServerMessage::Match(_) => "Match",
+            ServerMessage::Chat { .. } => "Chat",
+            ServerMessage::ChangeMap(_) => "ChangeMap",
+            ServerMessage::RconOutput(_) => "RconOutput",
+            ServerMessage::Cvars(_) => "Cvars",
+            ServerMessage::Tick(_) => "Tick",
+            ServerMessage::Pong(_) => "Pong",
+        }
+    }
+}
+
+impl ChannelMessage for ServerMessage {
+    fn channel(&self) -> Channel {
+        match self {
+            ServerMessage::AssignId(_) => Channel::Events,
+            ServerMessage::Ecs(_) => Channel::Snapshot,
+            ServerMessage::PhysicsState(..) => Channel::Bulk,
+            ServerMessage::PhysicsDiff(_) => Channel::Snapshot,
+            ServerMessage::Chunk(_) => Channel::Bulk,
+            ServerMessage::Kill(_) => Channel::Events,
+            ServerMessage::Despawn(_) => Channel::Events,
+            ServerMessage::Match(_) => Channel::Snapshot,
+            ServerMessage::Chat { .. } => Channel::Events,
+            ServerMessage::ChangeMap(_) => Channel::Events,
+            ServerMessage::RconOutput(_) => Channel::Events,
+            ServerMessage::Cvars(_) => Channel::Events,
+            ServerMessage::Tick(_) => Channel::Snapshot,
+            ServerMessage::Pong(_) => Channel::Events,
+        }
+    }
+}
+
+impl ChannelMessage for ClientMessage {
+    fn channel(&self) -> Channel {
+        match self {
+            ClientMessage::UserCmds(_) => Channel::Input,
+            ClientMessage::Shot { .. } => Channel::Events,
+            ClientMessage::Respawn => Channel::Events,
+            ClientMessage::Join => Channel::Events,
+            ClientMessage::Chat { .. } => Channel::Events,
+            ClientMessage::Rcon { .. } => Channel::Events,
+            ClientMessage::Ping(_) => Channel::Input,
+        }
+    }
 }
 
 type Column<T> = Vec<(Entity, T)>;
@@ -29,6 +101,13 @@ pub struct Columns {
     pub Player:          Column<Player>,
     pub PointObject:     Column<PointObject>,
     pub Properties:      Column<Properties>,
+    pub Score:           Column<Score>,
+    pub Team:            Column<Team>,
+    pub Flag:            Column<Flag>,
+    pub Item:            Column<Item>,
+    pub Water:           Column<Water>,
+    pub Transform:       Column<Transform>,
+    pub ModelId:         Column<ModelId>,
 }
 
 macro_rules! push {
@@ -37,6 +116,12 @@ macro_rules! push {
     }}
 }
 
+macro_rules! retain {
+    ($self:ident, $keep:ident, $t:tt) => {{
+        $self.$t.retain(|(id, _)| $keep(id));
+    }}
+}
+
 impl Columns {
     pub fn ids(&self) -> Vec<&Entity> {
         let mut ids = Vec::new();
@@ -46,6 +131,30 @@ impl Columns {
         push!(self, ids, Player);
         push!(self, ids, PointObject);
         push!(self, ids, Properties);
+        push!(self, ids, Score);
+        push!(self, ids, Team);
+        push!(self, ids, Flag);
+        push!(self, ids, Item);
+        push!(self, ids, Water);
+        push!(self, ids, Transform);
+        push!(self, ids, ModelId);
         ids
     }
+
+    // drops the components of every entity keep says no to
+    pub fn retain(&mut self, keep: impl Fn(&Entity) -> bool) {
+        retain!(self, keep, RigidBodyHandle);
+        retain!(self, keep, ColliderHandle);
+        retain!(self, keep, MeshWrapper);
+        retain!(self, keep, Player);
+        retain!(self, keep, PointObject);
+        retain!(self, keep, Properties);
+        retain!(self, keep, Score);
+        retain!(self, keep, Team);
+        retain!(self, keep, Flag);
+        retain!(self, keep, Item);
+        retain!(self, keep, Water);
+        retain!(self, keep, Transform);
+        retain!(self, keep, ModelId);
+    }
 }
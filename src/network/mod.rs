@@ -7,11 +7,25 @@ pub use setup::*;
 mod utils;
 pub use utils::*;
 
-// https://docs.rs/renet/1.2.0/src/renet/channel/mod.rs.html
-pub const NET_CHANNELS: [u8; 3] = [
-    0, // DefaultChannel::Unreliable
-    1, // DefaultChannel::ReliableUnordered
-    2, // DefaultChannel::ReliableOrdered
-];
+mod transfer;
+pub use transfer::*;
+
+mod channel;
+pub use channel::*;
+
+mod stats;
+pub use stats::*;
+
+mod socket;
+pub use socket::*;
+
+mod transport;
+pub use transport::*;
+
+mod loopback;
+pub use loopback::*;
 
 pub type ClientId = u64;
+
+// counts server ticks since start, the clock the server and clients agree on
+pub type Tick = u32;
@@ -1,24 +1,29 @@
 use std::{
-    net::UdpSocket,
+    net::{SocketAddr, UdpSocket},
     time::SystemTime,
 };
 
 use renet::{
-    ConnectionConfig, RenetClient, RenetServer
+    RenetClient, RenetServer
 };
 use renet_netcode::{
-    ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport,
-    ServerAuthentication, ServerConfig
+    ClientAuthentication, ServerAuthentication, ServerConfig
 };
 
-const PROTOCOL_ID: u64 = 7;
+use super::{connection_config, wrap_socket, ClientTransport, NetConditions, ServerTransport, Socket};
 
-pub fn create_client(addr: String) -> (RenetClient, NetcodeClientTransport) {
-    let addr = addr.parse().unwrap();
-    let connection_config = ConnectionConfig::default();
-    let client = RenetClient::new(connection_config);
+const PROTOCOL_ID: u64 = 8;
 
+pub fn create_client(addr: String, conditions: &NetConditions) -> (RenetClient, ClientTransport) {
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    socket.set_nonblocking(true).unwrap();
+    create_client_with_socket(addr.parse().unwrap(), wrap_socket(socket, conditions))
+}
+
+pub fn create_client_with_socket(addr: SocketAddr, socket: Box<dyn Socket>) -> (RenetClient, ClientTransport) {
+    let connection_config = connection_config();
+    let client = RenetClient::new(connection_config);
+
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
     let client_id = current_time.as_millis() as u64;
     let authentication = ClientAuthentication::Unsecure {
@@ -28,14 +33,20 @@ pub fn create_client(addr: String) -> (RenetClient, NetcodeClientTransport) {
         protocol_id: PROTOCOL_ID,
     };
 
-    let transport = NetcodeClientTransport::new(current_time, authentication, socket).unwrap();
+    let transport = ClientTransport::new(current_time, authentication, socket).unwrap();
 
     (client, transport)
 }
 
-pub fn create_server(addr: String) -> (RenetServer, NetcodeServerTransport) {
-    let addr = addr.parse().unwrap();
-    let connection_config = ConnectionConfig::default();
+pub fn create_server(addr: String, conditions: &NetConditions) -> (RenetServer, ServerTransport) {
+    let addr: SocketAddr = addr.parse().unwrap();
+    let socket: UdpSocket = UdpSocket::bind(addr).unwrap();
+    socket.set_nonblocking(true).unwrap();
+    create_server_with_socket(addr, wrap_socket(socket, conditions))
+}
+
+pub fn create_server_with_socket(addr: SocketAddr, socket: Box<dyn Socket>) -> (RenetServer, ServerTransport) {
+    let connection_config = connection_config();
     let server: RenetServer = RenetServer::new(connection_config);
 
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
@@ -47,8 +58,7 @@ pub fn create_server(addr: String) -> (RenetServer, NetcodeServerTransport) {
         authentication: ServerAuthentication::Unsecure,
     };
 
-    let socket: UdpSocket = UdpSocket::bind(addr).unwrap();
-    let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    let transport = ServerTransport::new(server_config, socket);
 
     (server, transport)
 }
@@ -0,0 +1,155 @@
+use crate::*;
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+// anything the netcode transports can send datagrams through
+
+pub trait Socket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<usize>;
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+}
+
+impl Socket for UdpSocket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        UdpSocket::send_to(self, buf, addr)
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+}
+
+// fake bad network conditions, applied to packets in both directions
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct NetConditions {
+    #[arg(long = "sim-latency", default_value_t = 0, help = "simulated one way latency in ms")]
+    pub latency: u64,
+    #[arg(long = "sim-jitter", default_value_t = 0, help = "simulated random extra latency in ms")]
+    pub jitter: u64,
+    #[arg(long = "sim-loss", default_value_t = 0.0, value_parser = parse_chance, help = "chance of dropping a packet (0-1)")]
+    pub loss: f32,
+    #[arg(long = "sim-duplicate", default_value_t = 0.0, value_parser = parse_chance, help = "chance of duplicating a packet (0-1)")]
+    pub duplicate: f32,
+}
+
+fn parse_chance(arg: &str) -> Result<f32, String> {
+    let chance: f32 = arg.parse().map_err(|err| format!("{}", err))?;
+    if (0.0..=1.0).contains(&chance) {
+        Ok(chance)
+    } else {
+        Err("expected a chance from 0 to 1".to_string())
+    }
+}
+
+impl NetConditions {
+    pub fn is_perfect(&self) -> bool {
+        self.latency == 0 && self.jitter == 0 && self.loss <= 0.0 && self.duplicate <= 0.0
+    }
+
+    // when each copy of a packet arrives, empty if it is lost
+    fn schedule(&self, now: Instant) -> Vec<Instant> {
+        if gen_range(0.0, 1.0) < self.loss {
+            return Vec::new();
+        }
+
+        let copies = if gen_range(0.0, 1.0) < self.duplicate { 2 } else { 1 };
+
+        (0..copies).map(|_| {
+            let jitter = if self.jitter > 0 { gen_range(0, self.jitter) } else { 0 };
+            now + Duration::from_millis(self.latency + jitter)
+        }).collect()
+    }
+}
+
+struct DelayedPacket {
+    at: Instant,
+    addr: SocketAddr,
+    data: Vec<u8>,
+}
+
+pub struct SimSocket<S: Socket> {
+    inner: S,
+    conditions: NetConditions,
+    outgoing: Vec<DelayedPacket>,
+    incoming: Vec<DelayedPacket>,
+}
+
+impl<S: Socket> SimSocket<S> {
+    pub fn new(inner: S, conditions: NetConditions) -> Self {
+        Self {
+            inner,
+            conditions,
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+        }
+    }
+
+    // a packet that fails to send is lost, like on a real network
+    fn flush_outgoing(&mut self, now: Instant) {
+        while let Some(i) = self.outgoing.iter().position(|packet| packet.at <= now) {
+            let packet = self.outgoing.swap_remove(i);
+            if let Err(err) = self.inner.send_to(&packet.data, packet.addr) {
+                eprintln!("failed to send to {}: {}", packet.addr, err);
+            }
+        }
+    }
+}
+
+impl<S: Socket> Socket for SimSocket<S> {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let now = Instant::now();
+        for at in self.conditions.schedule(now) {
+            self.outgoing.push(DelayedPacket { at, addr, data: buf.to_vec() });
+        }
+        self.flush_outgoing(now);
+        Ok(buf.len())
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let now = Instant::now();
+        self.flush_outgoing(now);
+
+        // move everything that has arrived into the delay queue
+        loop {
+            match self.inner.recv_from(buf) {
+                Ok((len, addr)) => {
+                    for at in self.conditions.schedule(now) {
+                        self.incoming.push(DelayedPacket { at, addr, data: buf[..len].to_vec() });
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // deliver the oldest due packet, in arrival time order
+        let due = self.incoming.iter()
+            .enumerate()
+            .filter(|(_, packet)| packet.at <= now)
+            .min_by_key(|(_, packet)| packet.at)
+            .map(|(i, _)| i);
+
+        match due {
+            Some(i) => {
+                let packet = self.incoming.remove(i);
+                let len = packet.data.len().min(buf.len());
+                buf[..len].copy_from_slice(&packet.data[..len]);
+                Ok((len, packet.addr))
+            }
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+pub fn wrap_socket(socket: UdpSocket, conditions: &NetConditions) -> Box<dyn Socket> {
+    if conditions.is_perfect() {
+        Box::new(socket)
+    } else {
+        Box::new(SimSocket::new(socket, conditions.clone()))
+    }
+}
@@ -0,0 +1,43 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use renet::NetworkInfo;
+
+// bytes and message counts per ServerMessage variant,
+// collected by the server between two log lines
+
+#[derive(Default)]
+pub struct MessageStats {
+    sizes: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl MessageStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, msg: &ServerMessage) {
+        let (count, bytes) = self.sizes.entry(msg.name()).or_default();
+        *count += 1;
+        *bytes += serialized_size(msg).unwrap_or(0);
+    }
+
+    pub fn log(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        for (name, (count, bytes)) in std::mem::take(&mut self.sizes) {
+            println!("[stats] {:<12} {:>6} msgs {:>10.1} KB/s {:>8} B/msg",
+                name, count, bytes as f64 / 1024.0 / secs, bytes / count.max(1));
+        }
+    }
+}
+
+pub fn format_network_info(info: &NetworkInfo) -> String {
+    format!("rtt {:.0} ms, loss {:.1}%, up {:.1} KB/s, down {:.1} KB/s",
+        info.rtt * 1000.0,
+        info.packet_loss * 100.0,
+        info.bytes_sent_per_second / 1024.0,
+        info.bytes_received_per_second / 1024.0,
+    )
+}
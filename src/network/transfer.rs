@@ -0,0 +1,167 @@
+use crate::*;
+
+use std::collections::{HashMap, VecDeque};
+
+// splits large payloads (such as the initial world) into chunks small enough
+// to not hit the channel memory limits, and puts them back together again
+
+pub const CHUNK_SIZE: usize = 16 * 1024;
+// larger transfers are dropped instead of allocated
+pub const MAX_TRANSFER_LEN: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub transfer: u32,
+    pub index: u32,
+    pub count: u32,
+    pub total_len: u64,
+    pub data: Vec<u8>,
+}
+
+struct OutgoingTransfer {
+    id: u32,
+    data: Vec<u8>,
+    next: u32,
+}
+
+impl OutgoingTransfer {
+    fn count(&self) -> u32 {
+        self.data.len().div_ceil(CHUNK_SIZE).max(1) as u32
+    }
+
+    fn next_chunk(&mut self) -> Option<Chunk> {
+        let count = self.count();
+        if self.next >= count {
+            return None;
+        }
+
+        let start = self.next as usize * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(self.data.len());
+        let chunk = Chunk {
+            transfer: self.id,
+            index: self.next,
+            count,
+            total_len: self.data.len() as u64,
+            data: self.data[start..end].to_vec(),
+        };
+
+        self.next += 1;
+        Some(chunk)
+    }
+}
+
+#[derive(Default)]
+pub struct TransferSender {
+    next_id: u32,
+    pending: VecDeque<OutgoingTransfer>,
+}
+
+impl TransferSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // queue a payload, returns the transfer id
+    pub fn send(&mut self, data: Vec<u8>) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending.push_back(OutgoingTransfer { id, data, next: 0 });
+        id
+    }
+
+    pub fn next_chunk(&mut self) -> Option<Chunk> {
+        while let Some(transfer) = self.pending.front_mut() {
+            if let Some(chunk) = transfer.next_chunk() {
+                return Some(chunk);
+            }
+            self.pending.pop_front();
+        }
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+struct IncomingTransfer {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: u32,
+    received_bytes: usize,
+    total_len: usize,
+}
+
+#[derive(Default)]
+pub struct TransferReceiver {
+    transfers: HashMap<u32, IncomingTransfer>,
+}
+
+impl TransferReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // returns the whole payload once the last missing chunk arrives,
+    // a chunk that doesn't add up drops its whole transfer
+    pub fn receive(&mut self, chunk: Chunk) -> Option<Vec<u8>> {
+        if !Self::is_valid(&chunk) {
+            eprintln!("dropping transfer {}: bad chunk {} of {}", chunk.transfer, chunk.index, chunk.count);
+            self.transfers.remove(&chunk.transfer);
+            return None;
+        }
+
+        let transfer = self.transfers.entry(chunk.transfer).or_insert_with(|| IncomingTransfer {
+            chunks: vec![None; chunk.count as usize],
+            received: 0,
+            received_bytes: 0,
+            total_len: chunk.total_len as usize,
+        });
+
+        if transfer.chunks.len() != chunk.count as usize || transfer.total_len != chunk.total_len as usize {
+            eprintln!("dropping transfer {}: chunks of different transfers", chunk.transfer);
+            self.transfers.remove(&chunk.transfer);
+            return None;
+        }
+
+        let slot = &mut transfer.chunks[chunk.index as usize];
+        if slot.is_none() {
+            transfer.received += 1;
+            transfer.received_bytes += chunk.data.len();
+            *slot = Some(chunk.data);
+        }
+
+        if transfer.received as usize == transfer.chunks.len() {
+            let transfer = self.transfers.remove(&chunk.transfer).unwrap();
+            let mut data = Vec::with_capacity(transfer.total_len);
+            for chunk in transfer.chunks {
+                data.extend(chunk.unwrap());
+            }
+            if data.len() != transfer.total_len {
+                eprintln!("dropping transfer {}: {} bytes instead of {}", chunk.transfer, data.len(), transfer.total_len);
+                return None;
+            }
+            return Some(data);
+        }
+
+        None
+    }
+
+    // the count has to be the one the sender gets for the length
+    fn is_valid(chunk: &Chunk) -> bool {
+        chunk.total_len <= MAX_TRANSFER_LEN as u64
+            && chunk.count as usize == (chunk.total_len as usize).div_ceil(CHUNK_SIZE).max(1)
+            && chunk.index < chunk.count
+            && chunk.data.len() <= CHUNK_SIZE
+    }
+
+    // (received bytes, total bytes) of all unfinished transfers
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.transfers.is_empty() {
+            return None;
+        }
+
+        Some(self.transfers.values().fold((0, 0), |(received, total), transfer| {
+            (received + transfer.received_bytes, total + transfer.total_len)
+        }))
+    }
+}
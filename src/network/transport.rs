@@ -0,0 +1,187 @@
+use std::{
+    io,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use renet::{ClientId, RenetClient, RenetServer};
+use renet_netcode::NetcodeTransportError;
+use renetcode::{
+    ClientAuthentication, NetcodeClient, NetcodeError, NetcodeServer,
+    ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES
+};
+
+use super::Socket;
+
+// same as the transports in renet_netcode,
+// but over any Socket instead of only UdpSocket
+
+pub struct ClientTransport {
+    socket: Box<dyn Socket>,
+    netcode_client: NetcodeClient,
+    buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+}
+
+impl ClientTransport {
+    pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: Box<dyn Socket>) -> Result<Self, NetcodeError> {
+        Ok(Self {
+            socket,
+            netcode_client: NetcodeClient::new(current_time, authentication)?,
+            buffer: [0; NETCODE_MAX_PACKET_BYTES],
+        })
+    }
+
+    pub fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+        if let Some(reason) = self.netcode_client.disconnect_reason() {
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+
+        for packet in client.get_packets_to_send() {
+            let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
+            self.socket.send_to(payload, addr)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, duration: Duration, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+        if let Some(reason) = self.netcode_client.disconnect_reason() {
+            client.disconnect_due_to_transport();
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+
+        if let Some(error) = client.disconnect_reason() {
+            let (addr, packet) = self.netcode_client.disconnect()?;
+            self.socket.send_to(packet, addr)?;
+            return Err(error.into());
+        }
+
+        if self.netcode_client.is_connected() {
+            client.set_connected();
+        } else if self.netcode_client.is_connecting() {
+            client.set_connecting();
+        }
+
+        loop {
+            let packet = match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => {
+                    if addr != self.netcode_client.server_addr() {
+                        continue;
+                    }
+                    &mut self.buffer[..len]
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some(payload) = self.netcode_client.process_packet(packet) {
+                client.process_packet(payload);
+            }
+        }
+
+        if let Some((packet, addr)) = self.netcode_client.update(duration) {
+            self.socket.send_to(packet, addr)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ServerTransport {
+    socket: Box<dyn Socket>,
+    netcode_server: NetcodeServer,
+    buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+}
+
+impl ServerTransport {
+    pub fn new(server_config: ServerConfig, socket: Box<dyn Socket>) -> Self {
+        Self {
+            socket,
+            netcode_server: NetcodeServer::new(server_config),
+            buffer: [0; NETCODE_MAX_PACKET_BYTES],
+        }
+    }
+
+    pub fn client_addr(&self, client_id: ClientId) -> Option<SocketAddr> {
+        self.netcode_server.client_addr(client_id)
+    }
+
+    pub fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), NetcodeTransportError> {
+        self.netcode_server.update(duration);
+
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => {
+                    let result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
+                    handle_server_result(result, self.socket.as_mut(), server);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for client_id in self.netcode_server.clients_id() {
+            let result = self.netcode_server.update_client(client_id);
+            handle_server_result(result, self.socket.as_mut(), server);
+        }
+
+        for client_id in server.disconnections_id() {
+            let result = self.netcode_server.disconnect(client_id);
+            handle_server_result(result, self.socket.as_mut(), server);
+        }
+
+        Ok(())
+    }
+
+    pub fn send_packets(&mut self, server: &mut RenetServer) {
+        'clients: for client_id in server.clients_id() {
+            for packet in server.get_packets_to_send(client_id).unwrap() {
+                match self.netcode_server.generate_payload_packet(client_id, &packet) {
+                    Ok((addr, payload)) => {
+                        if let Err(err) = self.socket.send_to(payload, addr) {
+                            eprintln!("failed to send packet to client {}: {}", client_id, err);
+                            continue 'clients;
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("failed to encrypt packet for client {}: {}", client_id, err);
+                        continue 'clients;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn handle_server_result(result: ServerResult, socket: &mut dyn Socket, server: &mut RenetServer) {
+    let mut send_packet = |packet: &[u8], addr: SocketAddr| {
+        if let Err(err) = socket.send_to(packet, addr) {
+            eprintln!("failed to send packet to {}: {}", addr, err);
+        }
+    };
+
+    match result {
+        ServerResult::None => {}
+        ServerResult::PacketToSend { payload, addr } => {
+            send_packet(payload, addr);
+        }
+        ServerResult::Payload { client_id, payload } => {
+            if let Err(err) = server.process_packet_from(payload, client_id) {
+                eprintln!("error while processing payload for {}: {}", client_id, err);
+            }
+        }
+        ServerResult::ClientConnected { client_id, addr, payload, .. } => {
+            server.add_connection(client_id);
+            send_packet(payload, addr);
+        }
+        ServerResult::ClientDisconnected { client_id, addr, payload } => {
+            server.remove_connection(client_id);
+            if let Some(payload) = payload {
+                send_packet(payload, addr);
+            }
+        }
+    }
+}
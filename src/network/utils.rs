@@ -8,6 +8,10 @@ pub fn serialize(data: impl Serialize) -> Result<Vec<u8>, Box<bincode::ErrorKind
     Ok(buffer)
 }
 
+pub fn serialized_size(data: &impl Serialize) -> Result<u64, Box<bincode::ErrorKind>> {
+    bincode::Options::serialized_size(bincode::options(), data)
+}
+
 pub fn deserialize<T: for<'a> Deserialize<'a>>(data: &renet::Bytes) -> Result<T, Box<bincode::ErrorKind>> {
     let data = data.to_vec();
     let options = bincode::options();
@@ -1,5 +1,9 @@
 use crate::shared::*;
 
+use rapier3d::data::Index;
+
+use std::collections::HashMap;
+
 pub type PhysicsDiff = (Vec<(RigidBodyHandle, RigidBody)>, Vec<(ColliderHandle, Collider)>);
 
 pub struct Physics {
@@ -7,6 +11,12 @@ pub struct Physics {
     pipeline: PhysicsPipeline,
 }
 
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Physics {
     pub fn new() -> Self {
         Self {
@@ -15,7 +25,10 @@ impl Physics {
         }
     }
 
-    pub fn step(&mut self) {
+    pub fn step(&mut self, dt: f32, gravity: f32) {
+        self.state.integration_parameters.dt = dt;
+        self.state.gravity = vector![0.0, -gravity, 0.0];
+
         self.pipeline.step(
             &self.state.gravity,
             &self.state.integration_parameters,
@@ -32,6 +45,14 @@ impl Physics {
         );
     }
 
+    // the colliders of a body become boxes of this size, for crouching
+    pub fn resize_box(&mut self, body: RigidBodyHandle, size: Vec3) {
+        let size = size / 2.0; // half extents
+        for col in self.get_rig(body).colliders().to_vec() {
+            self.get_col_mut(col).set_shape(SharedShape::cuboid(size.x, size.y, size.z));
+        }
+    }
+
     pub fn spawn_cube(&mut self, pos: Vec3, size: Vec3) -> (RigidBodyHandle, ColliderHandle) {
         let size = size / 2.0; // half extents
 
@@ -50,6 +71,51 @@ impl Physics {
         (rigid_body_handle, collider_handle)
     }
 
+    // a body moved by the game instead of the physics, for movers in the map
+    pub fn spawn_kinematic(&mut self, pos: Isometry<f32>, collider: Collider) -> RigidBodyHandle {
+        let rigid_body = RigidBodyBuilder::kinematic_position_based()
+            .pose(pos)
+            .build();
+
+        let rigid_body_handle = self.state.rigid_body_set.insert(rigid_body);
+        self.state.collider_set.insert_with_parent(collider, rigid_body_handle, &mut self.state.rigid_body_set);
+
+        rigid_body_handle
+    }
+
+    // a sensor without a body, for triggers placed in the map
+    pub fn spawn_sensor(&mut self, pos: Vec3, radius: f32) -> ColliderHandle {
+        let collider = ColliderBuilder::ball(radius)
+            .translation(conv_vec_1(pos))
+            .sensor(true)
+            .build();
+
+        self.state.collider_set.insert(collider)
+    }
+
+    // the colliders go with it
+    pub fn remove_body(&mut self, handle: RigidBodyHandle) {
+        self.state.rigid_body_set.remove(
+            handle,
+            &mut self.state.island_manager,
+            &mut self.state.collider_set,
+            &mut self.state.impulse_joint_set,
+            &mut self.state.multibody_joint_set,
+            true,
+        );
+    }
+
+    // bodies whose colliders currently overlap the sensor
+    pub fn sensor_bodies(&self, sensor: ColliderHandle) -> Vec<RigidBodyHandle> {
+        self.state.narrow_phase.intersection_pairs_with(sensor)
+            .filter(|(_, _, intersecting)| *intersecting)
+            .filter_map(|(a, b, _)| {
+                let other = if a == sensor { b } else { a };
+                self.get_col(other).parent()
+            })
+            .collect()
+    }
+
     pub fn get_physics_diff(&self) -> PhysicsDiff {
         // use rapier3d::data::HasModifiedFlag;
 
@@ -68,6 +134,71 @@ impl Physics {
         )
     }
 
+    pub fn query_pipeline<'a>(&'a self, filter: QueryFilter<'a>) -> QueryPipeline<'a> {
+        self.state.broad_phase.as_query_pipeline(
+            self.state.narrow_phase.query_dispatcher(),
+            &self.state.rigid_body_set,
+            &self.state.collider_set,
+            filter,
+        )
+    }
+
+    // does a box at pos overlap any non-map collider?
+    pub fn is_blocked(&self, pos: Vec3, size: Vec3, exclude: Option<RigidBodyHandle>) -> bool {
+        let size = size / 2.0; // half extents
+        let shape = Cuboid::new(vector![size.x, size.y, size.z]);
+        let mut filter = QueryFilter::exclude_fixed().exclude_sensors();
+        if let Some(handle) = exclude {
+            filter = filter.exclude_rigid_body(handle);
+        }
+
+        self.query_pipeline(filter)
+            .intersect_shape(Isometry::translation(pos.x, pos.y, pos.z), &shape)
+            .next()
+            .is_some()
+    }
+
+    // does a box at pos overlap anything solid, the map included?
+    pub fn overlaps(&self, pos: Vec3, size: Vec3, exclude: RigidBodyHandle) -> bool {
+        let size = size / 2.0; // half extents
+        let shape = Cuboid::new(vector![size.x, size.y, size.z]);
+        let filter = QueryFilter::default().exclude_sensors().exclude_rigid_body(exclude);
+
+        self.query_pipeline(filter)
+            .intersect_shape(Isometry::translation(pos.x, pos.y, pos.z), &shape)
+            .next()
+            .is_some()
+    }
+
+    // how far a box at pos falls until it lands on something, None if it's further than max
+    pub fn drop_distance(&self, pos: Vec3, size: Vec3, max: f32, exclude: RigidBodyHandle) -> Option<f32> {
+        let size = size / 2.0; // half extents
+        let shape = Cuboid::new(vector![size.x, size.y, size.z]);
+        let filter = QueryFilter::default().exclude_sensors().exclude_rigid_body(exclude);
+
+        self.query_pipeline(filter)
+            .cast_shape(
+                &Isometry::translation(pos.x, pos.y, pos.z),
+                &vector![0.0, -1.0, 0.0],
+                &shape,
+                ShapeCastOptions::with_max_time_of_impact(max),
+            )
+            .map(|(_, hit)| hit.time_of_impact)
+    }
+
+    // what is right below a player box of this height at pos?
+    pub fn ground(&self, pos: Vec3, height: f32, body: RigidBodyHandle) -> Option<ColliderHandle> {
+        let filter = QueryFilter::default().exclude_sensors().exclude_rigid_body(body);
+        let ray = Ray::new(conv_vec_1(pos).into(), vector![0.0, -1.0, 0.0]);
+        self.query_pipeline(filter).cast_ray(&ray, height / 2.0 + 0.1, true).map(|(col, _)| col)
+    }
+
+    // the velocity of a mover the collider belongs to, None for anything else
+    pub fn mover_velocity(&self, col: ColliderHandle) -> Option<Vec3> {
+        let body = self.get_rig(self.get_col(col).parent()?);
+        body.is_kinematic().then(|| conv_vec_2(*body.linvel()))
+    }
+
     pub fn get_rig(&self, handle: RigidBodyHandle) -> &RigidBody {
         self.state.rigid_body_set.get(handle).expect("invalid rigid body handle")
     }
@@ -100,6 +231,12 @@ pub struct PhysicsState {
     ccd_solver: CCDSolver,
 }
 
+impl Default for PhysicsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PhysicsState {
     pub fn new() -> Self {
         Self {
@@ -120,4 +257,128 @@ impl PhysicsState {
     pub fn set_valid(&mut self) {
         self.valid = true;
     }
+
+    // the client has to use the handles of the server, and once the server removed
+    // something the sets can't just be grown, so they are built again from a diff
+    // with everything in it. false if some handle could not be made
+    pub fn mirror(&mut self, (bodies, colliders): PhysicsDiff) -> bool {
+        self.rigid_body_set = RigidBodySet::new();
+        self.collider_set = ColliderSet::new();
+        self.island_manager = IslandManager::new();
+        self.broad_phase = DefaultBroadPhase::new();
+        self.narrow_phase = NarrowPhase::new();
+        self.impulse_joint_set = ImpulseJointSet::new();
+        self.multibody_joint_set = MultibodyJointSet::new();
+        self.ccd_solver = CCDSolver::new();
+
+        let bodies = bodies.into_iter().map(|(handle, body)| (handle.0, body)).collect();
+        let placed_bodies = place_at_handles(self, bodies,
+            |state, body| state.rigid_body_set.insert(body.unwrap_or_else(|| RigidBodyBuilder::fixed().build())).0,
+            |state, index| {
+                state.rigid_body_set.remove(
+                    RigidBodyHandle(index),
+                    &mut state.island_manager,
+                    &mut state.collider_set,
+                    &mut state.impulse_joint_set,
+                    &mut state.multibody_joint_set,
+                    false,
+                );
+            },
+        );
+
+        let colliders = colliders.into_iter().map(|(handle, col)| (handle.0, col)).collect();
+        let placed_colliders = place_at_handles(self, colliders,
+            |state, col| match col {
+                Some(col) => match col.parent() {
+                    Some(parent) if state.rigid_body_set.contains(parent) =>
+                        state.collider_set.insert_with_parent(col, parent, &mut state.rigid_body_set).0,
+                    _ => state.collider_set.insert(col).0,
+                },
+                None => state.collider_set.insert(ColliderBuilder::ball(0.0).build()).0,
+            },
+            |state, index| {
+                state.collider_set.remove(ColliderHandle(index), &mut state.island_manager, &mut state.rigid_body_set, false);
+            },
+        );
+
+        // the placeholders never made it into a step
+        self.collider_set.take_removed();
+
+        placed_bodies && placed_colliders
+    }
+}
+
+// an arena hands out its first free slot with its current generation, and the
+// generation only goes up when something is removed. so placeholders are pushed,
+// removed and put back until every value gets the index and generation it had
+fn place_at_handles<T>(
+    state: &mut PhysicsState,
+    mut values: Vec<(Index, T)>,
+    mut insert: impl FnMut(&mut PhysicsState, Option<T>) -> Index,
+    mut remove: impl FnMut(&mut PhysicsState, Index),
+) -> bool {
+    values.sort_by_key(|(handle, _)| {
+        let (index, generation) = handle.into_raw_parts();
+        (generation, index)
+    });
+
+    let mut placeholders: HashMap<u32, Index> = HashMap::new();
+    let mut len = 0;
+    let mut generation = 0;
+    let mut values = values.into_iter().peekable();
+
+    while let Some((first, _)) = values.peek() {
+        let group_generation = first.into_raw_parts().1;
+        let group: Vec<(u32, T)> = std::iter::from_fn(|| values.next_if(|(handle, _)| handle.into_raw_parts().1 == group_generation))
+            .map(|(handle, value)| (handle.into_raw_parts().0, value))
+            .collect();
+
+        // slots that exist already hold placeholders and have to be freed,
+        // the others are pushed at the end
+        let (mut freed, mut pushed): (Vec<_>, Vec<_>) = group.into_iter().partition(|(index, _)| *index < len);
+
+        while generation + (freed.len() as u32) < group_generation {
+            let Some((&slot, &placeholder)) = placeholders.iter().next() else {
+                placeholders.insert(len, insert(state, None));
+                if pushed.first().is_some_and(|(index, _)| *index == len) {
+                    freed.push(pushed.remove(0));
+                }
+                len += 1;
+                continue;
+            };
+
+            // back into the same slot, with the next generation
+            remove(state, placeholder);
+            generation += 1;
+            placeholders.insert(slot, insert(state, None));
+        }
+
+        if generation + freed.len() as u32 != group_generation {
+            return false;
+        }
+
+        // freed backwards, so they are handed out again in order
+        for (index, _) in freed.iter().rev() {
+            remove(state, placeholders.remove(index).unwrap());
+            generation += 1;
+        }
+        for (_, value) in freed {
+            insert(state, Some(value));
+        }
+
+        for (index, value) in pushed {
+            while len < index {
+                placeholders.insert(len, insert(state, None));
+                len += 1;
+            }
+            insert(state, Some(value));
+            len += 1;
+        }
+    }
+
+    for (_, placeholder) in placeholders {
+        remove(state, placeholder);
+    }
+
+    true
 }
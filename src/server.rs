@@ -1,130 +1,280 @@
-mod shared;
-mod physics;
-mod network;
-mod utils;
-mod components;
 use crate::shared::*;
+use crate::game::*;
+use crate::console::*;
+use crate::demo::*;
+use crate::gamemode::parse_game_mode;
 
-use renet::{RenetServer, ServerEvent, DefaultChannel};
-use renet_netcode::NetcodeServerTransport;
+use renet::{RenetServer, ServerEvent};
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::mpsc::Receiver;
 
-struct Server {
-    shared: Shared,
+// the network side of the server, the game itself is in Game
+pub struct Server {
+    pub game: Game,
     server: RenetServer,
-    transport: NetcodeServerTransport,
-    client_ids: HashMap<ClientId, Entity>,
+    transport: ServerTransport,
+    transfers: HashMap<ClientId, TransferSender>,
+    stats: MessageStats,
+    stats_interval: Option<Interval>,
+    bot_count: usize,
+    console: Option<Receiver<String>>,
+    rcon_password: Option<String>,
+    rcon_attempts: HashMap<IpAddr, RconAttempts>,
+    demo: Option<DemoRecorder>,
+    quit: bool,
+}
+
+// max chunks of queued transfers sent to each client per tick
+const CHUNKS_PER_TICK: usize = 4;
+
+// rcon attempts per second, and how many can be sent at once
+const RCON_RATE: f32 = 0.5;
+const RCON_BURST: f32 = 3.0;
+// bad passwords before the client is kicked, and refused until the address is forgotten
+const RCON_MAX_FAILURES: u32 = 5;
+// seconds without an attempt
+const RCON_FORGET_TIME: f32 = 600.0;
+
+// by address, the clients choose their own ids and can come back with a new one
+struct RconAttempts {
+    limit: RateLimit,
+    failures: u32,
+    last: f32,
 }
 
 impl Server {
-    fn create(addr: String) -> Self {
-        let (server, transport) = create_server(addr);
+    pub fn create(args: ServerArgs) -> Self {
+        let (server, transport) = create_server(args.common.addr.clone(), &args.common.conditions);
+        Self::new(args, server, transport, Some(read_stdin()))
+    }
+
+    // a listen server, the local client connects through the loopback
+    // and the others over the network like always, on the already bound remote socket
+    pub fn host(args: ServerArgs, local: LoopbackSocket, remote: UdpSocket) -> Self {
+        let addr = args.addr();
+        let socket = ListenSocket::new(local, wrap_socket(remote, &args.common.conditions));
+
+        let (server, transport) = create_server_with_socket(addr, Box::new(socket));
+        Self::new(args, server, transport, Some(read_stdin()))
+    }
+
+    // no console, used by tests with a loopback socket
+    pub fn with_socket(args: ServerArgs, socket: Box<dyn Socket>) -> Self {
+        let (server, transport) = create_server_with_socket(args.addr(), socket);
+        Self::new(args, server, transport, None)
+    }
+
+    fn new(args: ServerArgs, server: RenetServer, transport: ServerTransport, console: Option<Receiver<String>>) -> Self {
+        let mut cvars = Cvars::default();
+        if let Err(err) = cvars.apply_args(&args.common.cvars) {
+            ServerArgs::command().error(ErrorKind::ValueValidation, err).exit();
+        }
+
+        let demo = args.record.as_deref().map(DemoRecorder::create).transpose()
+            .unwrap_or_else(|err| ServerArgs::command().error(ErrorKind::Io, err).exit());
 
         Self {
-            shared: Shared::new(),
+            game: Game::new(&args.mode, args.map, cvars),
             server,
             transport,
-            client_ids: HashMap::new(),
+            transfers: HashMap::new(),
+            stats: MessageStats::new(),
+            stats_interval: (args.stats_interval > 0.0)
+                .then(|| Interval::new(Duration::from_secs_f32(args.stats_interval))),
+            bot_count: args.bots,
+            console,
+            rcon_password: args.rcon_password,
+            rcon_attempts: HashMap::new(),
+            demo,
+            quit: false,
         }
     }
 
-    async fn start(&mut self) {
-        self.shared.load_map(TEST_MAP.to_string()).await;
+    pub async fn start(&mut self) -> Result<(), String> {
+        self.init().await?;
 
-        let mut update = Interval::new(Duration::from_millis(1000 / 30));
-        let mut dt_accumulator = 0.0;
+        let mut ticker = Ticker::new(self.game.shared.cvars.sv_tick_rate);
 
-        loop {
-            if update.tick() {
-                let delta = update.delta();
-                dt_accumulator += delta.as_secs_f32();
+        while !self.quit {
+            ticker.set_rate(self.game.shared.cvars.sv_tick_rate);
+            ticker.wait().await;
 
-                self.network_receive(delta).await;
+            let start = Instant::now();
+            self.update(ticker.interval()).await;
+            ticker.finish(self.game.current_tick, start.elapsed());
+        }
 
-                while dt_accumulator >= PHYSICS_STEP {
-                    self.shared.handle_physics(PHYSICS_STEP).await;
-                    dt_accumulator -= PHYSICS_STEP;
-                }
+        self.server.disconnect_all();
+        self.transport.send_packets(&mut self.server);
+        Ok(())
+    }
+
+    pub async fn init(&mut self) -> Result<(), String> {
+        self.game.load_map().await?;
+        let msgs = self.stamp(self.game.world_messages());
+        self.record(&msgs);
+
+        // bots get client ids from the top, real ones are random
+        for i in 0..self.bot_count {
+            self.game.spawn_bot(ClientId::MAX - i as ClientId);
+        }
+        Ok(())
+    }
+
+    // one tick
+    pub async fn update(&mut self, delta: Duration) {
+        self.network_receive(delta).await;
+        self.game.tick(delta.as_secs_f32()).await;
+        self.network_send();
+        self.log_stats();
+        self.handle_console().await;
+    }
+
+    async fn change_map(&mut self, map: String) -> Result<(), String> {
+        self.game.change_map(map).await?;
 
-                self.network_send().await;
+        let mut msgs = vec![ServerMessage::ChangeMap(self.game.map.clone())];
+        msgs.extend(self.game.world_messages());
+        self.record(&self.stamp(msgs));
+
+        for client_id in self.server.clients_id() {
+            let mut msgs = vec![ServerMessage::ChangeMap(self.game.map.clone())];
+            msgs.extend(self.game.world_messages());
+            if let Some(id) = self.game.client_ids.get(&client_id) {
+                msgs.push(ServerMessage::AssignId(*id));
             }
+            self.queue_transfer(client_id, msgs);
         }
+
+        Ok(())
     }
 
-    async fn get_random_spawn(&self) -> Vec3 {
-        let mut spawn_points = Vec::new();
+    fn queue_transfer(&mut self, client_id: ClientId, msgs: ServerMessages) {
+        let data = serialize(msgs).unwrap();
+        self.transfers.entry(client_id).or_default().send(data);
+    }
 
-        for (_id, (pos, props)) in self.shared.ecs.query::<(&PointObject, &Properties)>().iter() {
-            if let Some(spawn) = props.spawn {
-                if spawn {
-                    spawn_points.push(pos.0);
+    async fn handle_console(&mut self) {
+        while let Some(Ok(line)) = self.console.as_ref().map(|console| console.try_recv()) {
+            match Command::parse(&line) {
+                Ok(command) => {
+                    let output = self.execute(command).await;
+                    if !output.is_empty() {
+                        println!("{}", output);
+                    }
                 }
+                Err(err) => println!("{}", err),
             }
         }
+    }
 
-        *spawn_points.choose().unwrap_or_else(|| &Vec3::ZERO)
+    async fn execute(&mut self, command: Command) -> String {
+        match command {
+            Command::Help => HELP.to_string(),
+            Command::Status => self.status(),
+            Command::Kick(client_id) => {
+                if self.game.remove_bot(client_id) {
+                    format!("removed bot {}", client_id)
+                } else if self.server.is_connected(client_id) {
+                    // cleaned up when the disconnect event comes in
+                    self.server.disconnect(client_id);
+                    format!("kicked {}", client_id)
+                } else {
+                    format!("no such client: {}", client_id)
+                }
+            }
+            Command::Map(map) => match self.change_map(map).await {
+                Ok(()) => format!("map is now {}", self.game.map),
+                Err(err) => err,
+            },
+            Command::Say(text) => {
+                self.game.announce(format!("console: {}", text));
+                String::new()
+            }
+            Command::Cvars => self.game.cvars(),
+            Command::Set(name, value) => match self.game.set(&name, &value) {
+                Ok(()) => format!("{} = {}", name, value),
+                Err(err) => err,
+            },
+            Command::Quit => {
+                self.quit = true;
+                "quitting".to_string()
+            }
+        }
     }
 
-    fn spawn_gibs(&mut self, target: Vec3) {
-        for x in 0..2 {
-            for z in 0..2 {
-                let x = (x * 2 - 1) as f32;
-                let z = (z * 2 - 1) as f32;
-                self.shared.ecs.spawn({
-                    let (rig, col) = self.shared.physics.spawn_cube(
-                        target + vec3(x / 2.0, 0.0, z / 2.0),
-                        vec3(0.5, 0.5, 0.5)
-                    );
-                    self.shared.physics.get_rig_mut(rig).set_linvel(vector![x, 0.0, z], false);
-                    (rig, col)
-                });
+    fn status(&self) -> String {
+        let state = &self.game.current_match.state;
+        let mut lines = vec![
+            format!("map: {}, mode: {}, phase: {:?}, time left: {:.0}s",
+                self.game.map, state.mode, state.phase, state.time_remaining),
+        ];
+
+        for client_id in self.server.clients_id() {
+            match self.game.client_ids.get(&client_id) {
+                Some(id) => {
+                    let score = self.game.shared.ecs.get::<&Score>(*id).map(|score| (*score).clone()).unwrap_or_default();
+                    lines.push(format!("{:>20} {:<12} frags {:>3} ping {:>4}", client_id, player_name(*id), score.frags, score.ping));
+                }
+                None => lines.push(format!("{:>20} spectator", client_id)),
             }
         }
 
-        self.shared.ecs.spawn({
-            let (rig, col) = self.shared.physics.spawn_cube(
-                target + vec3(0.0, 1.0, 0.0),
-                vec3(0.5, 0.5, 0.5)
-            );
-            self.shared.physics.get_rig_mut(rig).set_linvel(vector![0.0, 10.0, 0.0], false);
-            (rig, col)
-        });
+        for bot in &self.game.bots {
+            let id = self.game.client_ids[&bot.client_id];
+            let frags = self.game.shared.ecs.get::<&Score>(id).map(|score| score.frags).unwrap_or_default();
+            lines.push(format!("{:>20} {:<12} frags {:>3} bot", bot.client_id, player_name(id), frags));
+        }
+
+        lines.join("\n")
     }
 
-    async fn handle_msg(&mut self, cli_id: ClientId, msg: ClientMessage) {
-        let id = self.client_ids[&cli_id];
-        match msg {
-            ClientMessage::SetMoveState(state) => {
-                if let Ok(mut player) = self.shared.ecs.get::<&mut Player>(id) {
-                    let jumping = player.moves.jump;
-                    player.moves = state;
-                    player.moves.jump = jumping || player.moves.jump;
-                }
+    async fn rcon(&mut self, client_id: ClientId, password: String, command: String) -> String {
+        let Some(rcon_password) = &self.rcon_password else {
+            return "rcon is disabled".to_string();
+        };
+
+        let Some(ip) = self.transport.client_addr(client_id).map(|addr| addr.ip()) else {
+            return "rcon is disabled".to_string();
+        };
+
+        let time = self.game.time;
+        self.rcon_attempts.retain(|_, attempts| time - attempts.last < RCON_FORGET_TIME);
+        let attempts = self.rcon_attempts.entry(ip).or_insert_with(|| RconAttempts {
+            limit: RateLimit::new(RCON_RATE, RCON_BURST),
+            failures: 0,
+            last: time,
+        });
+        attempts.last = time;
+
+        if attempts.failures >= RCON_MAX_FAILURES {
+            println!("{}: kicked, {} had too many bad rcon passwords", client_id, ip);
+            self.server.disconnect(client_id);
+            return "too many bad rcon passwords".to_string();
+        }
+        if !attempts.limit.allow(time) {
+            return "too many rcon attempts".to_string();
+        }
+
+        if !constant_time_eq(rcon_password.as_bytes(), password.as_bytes()) {
+            attempts.failures += 1;
+            println!("{} ({}): bad rcon password ({})", client_id, ip, attempts.failures);
+
+            if attempts.failures >= RCON_MAX_FAILURES {
+                println!("{}: kicked after {} bad rcon passwords", client_id, attempts.failures);
+                self.server.disconnect(client_id);
             }
-            ClientMessage::SetYaw(yaw) => {
-                todo!()
-                // if let Ok(mut obj) = self.shared.ecs.get::<&mut PhysicsObject>(id) {
-                //     obj.set_yaw(yaw);
-                // }
-            }
-            ClientMessage::Shot(shot_id) => {
-                if let Some(target) = {
-                    if let Ok((handle, player)) = self.shared.ecs.query_one_mut::<(&RigidBodyHandle, &mut Player)>(shot_id) {
-                        player.hurt(20);
-                        if player.dead() {
-                            let obj = self.shared.physics.get_rig_mut(*handle);
-                            let old_pos = conv_vec_2(*obj.translation());
-                            obj.set_translation(vector![0.0, 60.0, 0.0], true);
-                            player.reset_hp();
-                            Some(old_pos)
-                        } else { None }
-                    } else { None }
-                } {
-                    self.spawn_gibs(target);
-                }
-            },
+            return "bad rcon password".to_string();
+        }
+        attempts.failures = 0;
+
+        println!("{} rcon: {}", client_id, command);
+        match Command::parse(&command) {
+            Ok(command) => self.execute(command).await,
+            Err(err) => err,
         }
     }
 
@@ -136,38 +286,21 @@ impl Server {
             match event {
                 ServerEvent::ClientConnected { client_id } => {
                     println!("{} connected", client_id);
-                    self.server.send_message(client_id, DefaultChannel::ReliableUnordered, serialize(
-                        vec![
-                            ServerMessage::AssignId({
-                                let (rig_handle, col_handle) = self.shared.physics.spawn_cube(
-                                    self.get_random_spawn().await,
-                                    vec3(1.0, 2.0, 1.0)
-                                );
-                                let id = self.shared.ecs.spawn((
-                                    Player::new(),
-                                    rig_handle,
-                                    col_handle,
-                                ));
-                                self.client_ids.insert(client_id, id);
-                                id
-                            }),
-                            ServerMessage::Ecs(Columns {
-                                MeshWrapper: clone_column!(self, &MeshWrapper),
-                                ..Columns::default()
-                            }),
-                            ServerMessage::PhysicsState(
-                                self.shared.physics.state.rigid_body_set.clone(),
-                                self.shared.physics.state.collider_set.clone()
-                            )
-                        ]
-                    ).unwrap());
+
+                    // everyone starts as a spectator, until they send Join
+                    let msgs = self.game.world_messages();
+                    self.queue_transfer(client_id, msgs);
+                },
+                ServerEvent::ClientDisconnected { client_id, reason } => {
+                    println!("{} disconnected: {}", client_id, reason);
+                    self.transfers.remove(&client_id);
+                    self.game.disconnect(client_id);
                 },
-                _ => {}
             }
         }
 
         for client in self.server.clients_id_iter().collect::<Vec<_>>().iter() {
-            for channel in NET_CHANNELS {
+            for channel in Channel::ALL {
                 while let Some(ref data) = self.server.receive_message(*client, channel) {
                     match deserialize::<ClientMessages>(data) {
                         Ok(new_msgs) =>
@@ -182,28 +315,187 @@ impl Server {
         }
     }
 
-    async fn network_send(&mut self) {
+    // rcon needs the console and pings are answered by the network side,
+    // everything else is up to the game
+    async fn handle_msg(&mut self, client_id: ClientId, msg: ClientMessage) {
+        match msg {
+            ClientMessage::Rcon { password, command } => {
+                let output = self.rcon(client_id, password, command).await;
+                self.send(client_id, vec![ServerMessage::RconOutput(output)]);
+            }
+            // answered after the tick, so the pong is stamped with the tick it leaves at
+            ClientMessage::Ping(time) => {
+                self.game.outbox.push((Recipient::Client(client_id), vec![ServerMessage::Pong(time)]));
+            }
+            msg => self.game.handle_msg(client_id, msg),
+        }
+    }
+
+    fn network_send(&mut self) {
+        for (client, id) in self.game.client_ids.iter() {
+            if let (Ok(info), Ok(mut score)) = (self.server.network_info(*client), self.game.shared.ecs.get::<&mut Score>(*id)) {
+                score.ping = (info.rtt * 1000.0) as u32;
+            }
+        }
+
+        for (recipient, msgs) in std::mem::take(&mut self.game.outbox) {
+            match recipient {
+                Recipient::All => self.broadcast(msgs),
+                Recipient::Client(client_id) => self.send(client_id, msgs),
+                Recipient::Spectators => {
+                    let spectators: Vec<ClientId> = self.server.clients_id_iter()
+                        .filter(|client| !self.game.client_ids.contains_key(client))
+                        .collect();
+                    self.multicast(&spectators, msgs);
+                }
+            }
+        }
+
         for client in self.server.clients_id_iter().collect::<Vec<_>>().iter() {
-            self.server.send_message(*client, DefaultChannel::Unreliable, serialize(
-                vec![
-                    ServerMessage::Ecs(Columns {
-                        Player: clone_column!(self, &Player),
-                        RigidBodyHandle: clone_column!(self, &RigidBodyHandle),
-                        ColliderHandle: clone_column!(self, &ColliderHandle),
-                        ..Columns::default()
-                    }),
-                    ServerMessage::PhysicsDiff(self.shared.physics.get_physics_diff())
-                ]
-            ).unwrap());
+            for _ in 0..CHUNKS_PER_TICK {
+                if !self.server.can_send_message(*client, Channel::Bulk, CHUNK_SIZE * 2) {
+                    break;
+                }
+
+                match self.transfers.get_mut(client).and_then(|transfer| transfer.next_chunk()) {
+                    Some(chunk) => self.send(*client, vec![ServerMessage::Chunk(chunk)]),
+                    None => break,
+                }
+            }
+
+            // don't waste bandwidth on snapshots before the world is loaded
+            if self.transfers.get(client).is_some_and(|transfer| !transfer.is_empty()) {
+                continue;
+            }
+
+            let msgs = self.game.snapshot_messages();
+            self.send(*client, msgs);
         }
 
         self.transport.send_packets(&mut self.server);
+
+        if self.demo.is_some() {
+            let msgs = self.stamp(self.game.snapshot_messages());
+            self.record(&msgs);
+            self.demo.as_mut().unwrap().flush();
+        }
+    }
+
+    // the demo sees what a spectator would, messages sent to single clients are left out
+    fn record(&mut self, msgs: &ServerMessages) {
+        if let Some(demo) = &mut self.demo {
+            demo.record(msgs);
+        }
+    }
+
+    // every batch starts with the tick, so the clients can order them
+    fn stamp(&self, msgs: ServerMessages) -> ServerMessages {
+        let mut stamped = vec![ServerMessage::Tick(self.game.current_tick)];
+        stamped.extend(msgs);
+        stamped
+    }
+
+    fn send(&mut self, client: ClientId, msgs: ServerMessages) {
+        if self.stats_interval.is_some() {
+            for msg in &msgs {
+                self.stats.record(msg);
+            }
+        }
+
+        for (channel, batch) in split_by_channel(msgs) {
+            self.server.send_message(client, channel, serialize(self.stamp(batch)).unwrap());
+        }
+    }
+
+    // the same messages to some of the clients, serialized once
+    fn multicast(&mut self, clients: &[ClientId], msgs: ServerMessages) {
+        if self.stats_interval.is_some() {
+            for _ in clients {
+                for msg in &msgs {
+                    self.stats.record(msg);
+                }
+            }
+        }
+
+        for (channel, batch) in split_by_channel(msgs) {
+            let data = serialize(self.stamp(batch)).unwrap();
+            for client in clients {
+                self.server.send_message(*client, channel, data.clone());
+            }
+        }
+    }
+
+    fn broadcast(&mut self, msgs: ServerMessages) {
+        if self.stats_interval.is_some() {
+            for _ in self.server.clients_id_iter() {
+                for msg in &msgs {
+                    self.stats.record(msg);
+                }
+            }
+        }
+
+        for (channel, batch) in split_by_channel(msgs) {
+            let batch = self.stamp(batch);
+            self.record(&batch);
+            self.server.broadcast_message(channel, serialize(batch).unwrap());
+        }
+    }
+
+    fn log_stats(&mut self) {
+        let Some(interval) = &mut self.stats_interval else { return };
+        if !interval.tick() {
+            return;
+        }
+
+        for client in self.server.clients_id_iter() {
+            if let Ok(info) = self.server.network_info(client) {
+                println!("[stats] client {}: {}", client, format_network_info(&info));
+            }
+        }
+
+        self.stats.log(interval.delta());
     }
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    let args = Args::parse();
-    let mut server = Server::create(args.addr);
-    server.start().await;
+// takes as long no matter where the passwords differ, only the length leaks
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = (0..a.len().max(b.len()))
+        .fold(a.len() ^ b.len(), |diff, i| {
+            diff | (a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0)) as usize
+        });
+    diff == 0
+}
+
+#[derive(Parser)]
+pub struct ServerArgs {
+    #[command(flatten)]
+    common: Args,
+    #[arg(long, default_value_t = 0.0, value_parser = parse_seconds, help = "log network stats every n seconds")]
+    stats_interval: f32,
+    #[arg(long, default_value = "dm", value_parser = parse_game_mode, help = "game mode: dm, tdm or ctf")]
+    mode: String,
+    #[arg(long, default_value_t = 0, help = "number of bots")]
+    bots: usize,
+    #[arg(long, default_value = TEST_MAP, help = "path of the map")]
+    map: String,
+    #[arg(long, help = "allow remote commands with this password")]
+    rcon_password: Option<String>,
+    #[arg(long, value_name = "PATH", help = "record a demo of the match")]
+    record: Option<String>,
+}
+
+impl ServerArgs {
+    pub fn addr(&self) -> SocketAddr {
+        self.common.addr.parse().unwrap()
+    }
+}
+
+// anything a Duration can hold, 0 turns it off
+fn parse_seconds(arg: &str) -> Result<f32, String> {
+    let secs: f32 = arg.parse().map_err(|err| format!("{}", err))?;
+    if secs >= 0.0 && Duration::try_from_secs_f32(secs).is_ok() {
+        Ok(secs)
+    } else {
+        Err("expected a number of seconds, 0 or more".to_string())
+    }
 }
@@ -16,7 +16,7 @@ pub use rapier3d::{
         math::{Isometry, Point},
         utils::obb as points_to_obb,
         na::{Vector3, UnitQuaternion, Quaternion, Matrix4, Vector4},
-        query::{Ray, RayCast},
+        query::{Ray, RayCast, ShapeCastOptions},
     },
 };
 pub use serde::{Deserialize, Serialize};
@@ -25,18 +25,17 @@ pub use crate::network::*;
 pub use crate::utils::*;
 pub use crate::components::*;
 pub use crate::physics::*;
+pub use crate::cvars::*;
 
-use gltf::{
-    image::Source,
-    scene::Transform,
-};
+use crate::movement::*;
+
+use gltf::image::Source;
 use serde_json::{
     from_value,
     from_str,
 };
 
-pub const PHYSICS_STEP: f32 = 1.0 / 60.0;
-pub const TEST_MAP: &'static str = "maps/test.glb";
+pub const TEST_MAP: &str = "maps/test.glb";
 
 // convert vectors between Vec3 (glam, macroquad) and Vector3 (nalgebra, rapier)
 // TODO create custom conversion trait such as .conv()?
@@ -49,24 +48,32 @@ pub fn conv_vec_2(vector: Vector3<f32>) -> Vec3 {
     vec3(vector.x, vector.y, vector.z)
 }
 
+pub fn conv_quat_1(quat: Quat) -> UnitQuaternion<f32> {
+    UnitQuaternion::new_normalize(Quaternion::new(quat.w, quat.x, quat.y, quat.z))
+}
+
+pub fn conv_quat_2(quat: UnitQuaternion<f32>) -> Quat {
+    Quat::from_xyzw(quat.i, quat.j, quat.k, quat.w)
+}
+
 // stupid trait used once
 
 trait AssumeOne: Iterator + Sized {
-    fn assume_one(self) -> Self::Item;
+    fn assume_one(self, what: &str) -> Result<Self::Item, String>;
 }
 
 impl<I> AssumeOne for I
 where
     I: Iterator,
 {
-    fn assume_one(mut self) -> Self::Item {
+    fn assume_one(mut self, what: &str) -> Result<Self::Item, String> {
         match self.next() {
-            None => panic!("assume_one failed: iterator is empty"),
+            None => Err(format!("no {} found", what)),
             Some(first) => {
                 if self.next().is_some() {
-                    eprintln!("assume_one warning: iterator had more than one item (ignoring)");
+                    eprintln!("assume_one warning: more than one {} (ignoring the rest)", what);
                 }
-                first
+                Ok(first)
             }
         }
     }
@@ -77,6 +84,13 @@ where
 pub struct Shared {
     pub ecs: hecs::World,
     pub physics: Physics,
+    pub cvars: Cvars,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Shared {
@@ -84,110 +98,232 @@ impl Shared {
         Self {
             ecs: hecs::World::new(),
             physics: Physics::new(),
+            cvars: Cvars::default(),
         }
     }
 
-    pub async fn load_map(&mut self, path: String) {
-        let (document, buffers, images) = gltf::import(path).unwrap();
-        let scene = document.scenes().assume_one();
+    pub async fn load_map(&mut self, path: &str) -> Result<(), String> {
+        let error = |err: String| format!("failed to load {}: {}", path, err);
+        let (document, buffers, images) = gltf::import(path).map_err(|err| error(err.to_string()))?;
+        let scene = document.scenes().assume_one("scene").map_err(error)?;
 
         for node in scene.nodes() {
             let mut builder = EntityBuilder::new();
 
+            let props: Option<Properties> = node.extras().as_ref()
+                .map(|extras| from_str(extras.get()).and_then(from_value))
+                .transpose()
+                .map_err(|err| error(format!("bad extras on node {}: {}", node.name().unwrap_or("unnamed"), err)))?;
+            let water = props.as_ref().is_some_and(|props| props.water == Some(true));
+
             if let Some(mesh) = node.mesh() {
-                builder.add_bundle(self.handle_mesh(&buffers, &images, &node, &mesh));
+                let (pos, rot, scale) = node.transform().decomposed();
+                let transform = Transform {
+                    pos: pos.into(),
+                    rot: Quat::from_array(rot),
+                    scale: scale.into(),
+                };
+
+                let mut wrapper = Self::handle_mesh(&buffers, &images, &mesh).map_err(error)?;
+                if water {
+                    // no collider, so it can be swum through
+                    builder.add(Water::around(&wrapper, &transform));
+                    for vertex in &mut wrapper.vertices {
+                        vertex.color[3] = 128;
+                    }
+                } else if let Some(mover) = props.as_ref().and_then(|props| props.mover.as_ref()) {
+                    // the body has the position and rotation, only the scale goes into the collider
+                    let collider = trimesh_collider(&wrapper, Mat4::from_scale(transform.scale)).map_err(error)?;
+                    let (handle, mover) = self.spawn_mover(&transform, collider, mover);
+                    builder.add(handle).add(mover);
+                } else {
+                    // the handle is not added to the ecs
+                    self.physics.state.collider_set.insert(trimesh_collider(&wrapper, transform.matrix()).map_err(error)?);
+                }
+                builder.add(wrapper).add(transform);
             } else {
                 let (pos, _, _) = node.transform().decomposed();
                 builder.add(PointObject(pos.into()));
             }
 
-            if let Some(extras) = node.extras() {
-                let props: Properties = from_value(from_str(extras.get()).unwrap()).unwrap();
+            if let Some(props) = props {
                 builder.add(props);
             }
 
             self.ecs.spawn(builder.build());
         }
+
+        Ok(())
+    }
+
+    // every node with a mesh, for models that are drawn on top of entities
+    pub fn load_model(path: &str) -> Result<Vec<(MeshWrapper, Transform)>, String> {
+        let error = |err: String| format!("failed to load {}: {}", path, err);
+        let (document, buffers, images) = gltf::import(path).map_err(|err| error(err.to_string()))?;
+        let scene = document.scenes().assume_one("scene").map_err(error)?;
+
+        let mut parts = Vec::new();
+        for node in scene.nodes() {
+            Self::handle_node(&buffers, &images, &node, Mat4::IDENTITY, &mut parts).map_err(error)?;
+        }
+        Ok(parts)
     }
 
-    fn handle_mesh(&mut self,
-            buffers: &Vec<gltf::buffer::Data>,
-            images: &Vec<gltf::image::Data>,
+    // the meshes of a node and its children, with the transforms of the parents applied
+    fn handle_node(
+            buffers: &[gltf::buffer::Data],
+            images: &[gltf::image::Data],
             node: &gltf::Node,
-            mesh: &gltf::Mesh) -> impl DynamicBundle {
+            parent: Mat4,
+            parts: &mut Vec<(MeshWrapper, Transform)>) -> Result<(), String> {
+
+        let matrix = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            let (scale, rot, pos) = matrix.to_scale_rotation_translation();
+            let wrapper = Self::handle_mesh(buffers, images, &mesh)?;
+            parts.push((wrapper, Transform { pos, rot, scale }));
+        }
+
+        for child in node.children() {
+            Self::handle_node(buffers, images, &child, matrix, parts)?;
+        }
+        Ok(())
+    }
 
-        let primitive = mesh.primitives().assume_one();
-        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    // the mesh of a node without the node transform.
+    // models may have no texture, then the color of the material is used
+    fn handle_mesh(
+            buffers: &[gltf::buffer::Data],
+            images: &[gltf::image::Data],
+            mesh: &gltf::Mesh) -> Result<MeshWrapper, String> {
+
+        let name = mesh.name().unwrap_or("unnamed");
+        let primitive = mesh.primitives().assume_one("primitive")?;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
         let indices: Vec<_> = reader
             .read_indices()
-            .map(|indices| indices.into_u32().collect()).unwrap();
+            .map(|indices| indices.into_u32().collect())
+            .ok_or_else(|| format!("mesh {} has no indices", name))?;
         let mut vertices: Vec<_> = reader
             .read_positions()
-            .unwrap()
-            .map(|pos| {
-                let tr = node.transform().matrix();
-                let tr = Matrix4::from(tr);
-                let p = tr * Vector4::new(pos[0], pos[1], pos[2], 1.0);
-                Vertex::new(p.x / p.w, p.y / p.w, p.z / p.w, 0.0, 0.0, WHITE)
-            })
+            .ok_or_else(|| format!("mesh {} has no positions", name))?
+            .map(|pos| Vertex::new(pos[0], pos[1], pos[2], 0.0, 0.0, WHITE))
             .collect();
-        for (i, uv) in reader.read_tex_coords(0).unwrap().into_f32().enumerate() {
-            vertices[i].uv = uv.into();
+        if let Some(uvs) = reader.read_tex_coords(0) {
+            for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+                vertex.uv = uv.into();
+            }
+        }
+
+        // the meshes of macroquad have 16 bit indices
+        if vertices.len() > u16::MAX as usize || indices.iter().any(|i| *i as usize >= vertices.len()) {
+            return Err(format!("mesh {} has too many vertices or a bad index", name));
+        }
+
+        let material = primitive.material().pbr_metallic_roughness();
+        let factor = material.base_color_factor();
+        let mut colors = vec![[255; 4]; vertices.len()];
+        if let Some(vertex_colors) = reader.read_colors(0) {
+            colors = vertex_colors.into_rgba_u8().collect();
+        }
+        for color in &mut colors {
+            for (channel, factor) in color.iter_mut().zip(factor) {
+                *channel = (*channel as f32 * factor) as u8;
+            }
         }
-        let mut texture = images[
-                primitive
-                .material()
-                .pbr_metallic_roughness()
-                .base_color_texture().unwrap()
-                .texture().source().index()
-            ].clone();
-        texture.pixels = texture.pixels
-            .chunks_exact(3)
-            .map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
-            .flatten().collect();
-
-        // create collider
-        // the handler is not added to the ecs
-
-        self.physics.state.collider_set.insert(
-            ColliderBuilder::trimesh(
-                vertices
-                    .clone()
-                    .iter()
-                    .map(|v| Point::new(v.position.x, v.position.y, v.position.z))
-                    .collect::<Vec<_>>(),
-                indices
-                    .chunks(3)
-                    .map(|i| [i[0], i[1], i[2]])
-                    .collect::<Vec<_>>()
-            )
-            .unwrap()
-            .restitution(0.5)
-            .build()
-        );
-
-        // added to the ecs:
-
-        (
-            MeshWrapper {
-                vertices: vertices.iter().map(|v| VertexWrapper {
-                    position: vec3(v.position.x, v.position.y, v.position.z),
-                    color: [255, 255, 255, 255],
-                    uv: vec2(v.uv.x, v.uv.y),
-                    normal: vec4(v.normal.x, v.normal.y, v.normal.z, 1.0),
-                }).collect(),
-                indices: indices.iter().map(|i| *i as u16).collect(),
-                texture: Some(ImageWrapper {
-                    width: texture.width.try_into().unwrap(),
-                    height: texture.height.try_into().unwrap(),
-                    bytes: texture.pixels,
-                }),
-            },
-        )
+
+        let texture = material.base_color_texture().map(|info| {
+            let texture = images.get(info.texture().source().index())
+                .ok_or_else(|| format!("mesh {} has a missing texture", name))?;
+            let bytes = match texture.format {
+                gltf::image::Format::R8G8B8 => texture.pixels
+                    .chunks_exact(3)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect(),
+                gltf::image::Format::R8G8B8A8 => texture.pixels.clone(),
+                format => return Err(format!("mesh {} has a texture in {:?}", name, format)),
+            };
+
+            Ok(ImageWrapper {
+                width: texture.width.try_into().map_err(|_| format!("texture of mesh {} is too large", name))?,
+                height: texture.height.try_into().map_err(|_| format!("texture of mesh {} is too large", name))?,
+                bytes,
+            })
+        }).transpose()?;
+
+        Ok(MeshWrapper {
+            vertices: vertices.iter().zip(colors).map(|(v, color)| VertexWrapper {
+                position: vec3(v.position.x, v.position.y, v.position.z),
+                color,
+                uv: vec2(v.uv.x, v.uv.y),
+                normal: vec4(v.normal.x, v.normal.y, v.normal.z, 1.0),
+            }).collect(),
+            indices: indices.iter().map(|i| *i as u16).collect(),
+            texture,
+        })
+    }
+
+    // the body of a mover and its state, the body starts at the transform
+    pub fn spawn_mover(&mut self, transform: &Transform, collider: Collider, props: &MoverProps) -> (RigidBodyHandle, Mover) {
+        let handle = self.physics.spawn_kinematic(transform.isometry(), collider);
+
+        let sensor = props.trigger.map(|radius| {
+            let col = self.physics.get_rig(handle).colliders()[0];
+            let center = conv_vec_2(self.physics.get_col(col).compute_aabb().center().coords);
+            self.physics.spawn_sensor(center, radius)
+        });
+
+        (handle, Mover::new(transform.pos, props, sensor))
+    }
+
+    pub fn body_position(&self, id: Entity) -> Option<Vec3> {
+        let handle = self.ecs.get::<&RigidBodyHandle>(id).ok()?;
+        Some(conv_vec_2(*self.physics.get_rig(*handle).translation()))
     }
 
-    pub async fn handle_physics(&mut self, _dt: f32) {
-        self.physics.step();
+    // where a player looks from
+    pub fn eye_position(&self, id: Entity) -> Option<Vec3> {
+        let pos = self.body_position(id)?;
+        let player = self.ecs.get::<&Player>(id).ok()?;
+        Some(pos + vec3(0.0, player.eye_height() - player.size().y / 2.0, 0.0))
+    }
+
+    // first thing hit by the ray, and the entity owning it (map geometry has none)
+    pub fn ray_intersection(&self, origin: Vec3, dir: Vec3, exclude: &[Entity]) -> Option<(Vec3, Option<Entity>)> {
+        let excluded: Vec<RigidBodyHandle> = exclude.iter()
+            .filter_map(|id| self.ecs.get::<&RigidBodyHandle>(*id).ok().map(|handle| *handle))
+            .collect();
+        let predicate = |_, col: &Collider| col.parent().is_none_or(|parent| !excluded.contains(&parent));
+        let filter = QueryFilter::default().exclude_sensors().predicate(&predicate);
+
+        let ray = Ray::new(conv_vec_1(origin).into(), conv_vec_1(dir));
+        let (col, toi) = self.physics.query_pipeline(filter).cast_ray(&ray, 1000.0, true)?;
+        let point = origin + dir * toi;
+
+        let owner = self.physics.get_col(col).parent().and_then(|parent| self.body_owner(parent));
+
+        Some((point, owner))
+    }
+
+    pub fn body_owner(&self, body: RigidBodyHandle) -> Option<Entity> {
+        self.ecs.query::<&RigidBodyHandle>().iter()
+            .find(|(_, handle)| **handle == body)
+            .map(|(id, _)| id)
+    }
+
+    pub async fn handle_physics(&mut self, dt: f32) {
+        self.handle_movement(dt);
+        self.physics.step(dt, self.cvars.sv_gravity);
+        self.sync_transforms();
+    }
+
+    // meshes with a body are drawn where the body is
+    pub fn sync_transforms(&mut self) {
+        for (_, (transform, handle)) in self.ecs.query_mut::<(&mut Transform, &RigidBodyHandle)>() {
+            if let Some(body) = self.physics.state.rigid_body_set.get(*handle) {
+                transform.set_isometry(body.position());
+            }
+        }
     }
 
     // pub async fn _handle_physics(&mut self, dt: f32) {
@@ -245,26 +381,43 @@ impl Shared {
     //     }
     // }
 
-    // fn handle_movement(_dt: f32, state: &mut MoveState, obj: &mut PhysicsObject) {
-    //     let move_speed = 0.1;
-    //     let step_ws = obj.yaw_vec() * move_speed;
-    //     let step_ad = vec3(-step_ws.z, 0.0, step_ws.x);
+    // the client runs this too for its own body
+    fn handle_movement(&mut self, dt: f32) {
+        let waters: Vec<Water> = self.ecs.query::<&Water>().iter().map(|(_, water)| water.clone()).collect();
 
-    //     if state.forward    { obj.vel += step_ws; }
-    //     if state.back       { obj.vel -= step_ws; }
-    //     if state.left       { obj.vel -= step_ad; }
-    //     if state.right      { obj.vel += step_ad; }
+        for (_, (player, handle)) in self.ecs.query_mut::<(&mut Player, &RigidBodyHandle)>() {
+            let body = self.physics.get_rig(*handle);
+            if player.dead() || !body.is_dynamic() || !body.is_enabled() {
+                continue;
+            }
 
-    //     if state.get_jump() && obj.on_ground {
-    //         obj.vel.y += 5.0;
-    //     }
-    // }
+            move_player(&mut self.physics, *handle, player, &waters, &self.cvars, dt);
+        }
+    }
+}
+
+// the vertices of the mesh go through the matrix
+fn trimesh_collider(mesh: &MeshWrapper, matrix: Mat4) -> Result<Collider, String> {
+    Ok(ColliderBuilder::trimesh(
+        mesh.vertices.iter()
+            .map(|v| matrix.transform_point3(v.position))
+            .map(|p| Point::new(p.x, p.y, p.z))
+            .collect::<Vec<_>>(),
+        mesh.indices.chunks(3).map(|i| [i[0] as u32, i[1] as u32, i[2] as u32]).collect::<Vec<_>>(),
+    )
+    .map_err(|err| err.to_string())?
+    .restitution(0.5)
+    .build())
 }
 
-pub use clap::{Parser, arg};
+pub use clap::{Parser, Subcommand, CommandFactory, arg, error::ErrorKind};
 
 #[derive(Parser)]
 pub struct Args {
     #[arg(help = "ip:port")]
     pub addr: String,
+    #[arg(long = "set", value_name = "CVAR=VALUE", help = "set a cvar, can be repeated")]
+    pub cvars: Vec<String>,
+    #[command(flatten)]
+    pub conditions: NetConditions,
 }
@@ -1,34 +1,31 @@
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
 pub struct Interval {
-    interval: u64,
-    last: u64,
+    interval: Duration,
+    last: Option<Instant>,
     delta: Duration,
 }
 
 impl Interval {
     pub fn new(duration: Duration) -> Self {
         Self {
-            interval: duration.as_millis().try_into().unwrap(),
-            last: 0,
+            interval: duration,
+            last: None,
             delta: Duration::ZERO,
         }
     }
 
     pub fn tick(&mut self) -> bool {
-        let now = SystemTime::UNIX_EPOCH.elapsed().unwrap();
-        let now: u64 = now.as_millis().try_into().unwrap();
+        let now = Instant::now();
 
-        if self.last + self.interval < now {
-            self.delta =
-                if self.last != 0 {
-                    Duration::from_millis(now - self.last)
-                } else { Duration::ZERO };
-            self.last = now;
-            return true;
+        match self.last {
+            Some(last) if now - last < self.interval => false,
+            last => {
+                self.delta = last.map_or(Duration::ZERO, |last| now - last);
+                self.last = Some(now);
+                true
+            }
         }
-
-        return false;
     }
 
     pub fn delta(&self) -> Duration {
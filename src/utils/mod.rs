@@ -1,2 +1,8 @@
 mod interval;
 pub use interval::*;
+
+mod rate_limit;
+pub use rate_limit::*;
+
+mod ticker;
+pub use ticker::*;
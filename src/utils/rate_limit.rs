@@ -0,0 +1,32 @@
+// token bucket, allows bursts of up to `burst` and then `rate` per second
+
+pub struct RateLimit {
+    rate: f32,
+    burst: f32,
+    tokens: f32,
+    last: f32,
+}
+
+impl RateLimit {
+    pub fn new(rate: f32, burst: f32) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: 0.0,
+        }
+    }
+
+    // time in seconds
+    pub fn allow(&mut self, time: f32) -> bool {
+        self.tokens = (self.tokens + (time - self.last) * self.rate).min(self.burst);
+        self.last = time;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::*;
+
+use std::time::{Duration, Instant};
+
+// runs the server at a fixed rate, sleeping on a tokio timer until the next tick.
+// every tick simulates exactly one interval, late ticks run right away to catch up
+
+pub struct Ticker {
+    interval: Duration,
+    next: Instant,
+    last_warning: Option<Instant>,
+}
+
+// ticks, further behind than this the missed ones are dropped
+const MAX_CATCH_UP: u32 = 5;
+
+// so a slow server doesn't flood the log
+const WARNING_INTERVAL: Duration = Duration::from_secs(5);
+
+impl Ticker {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / rate,
+            next: Instant::now(),
+            last_warning: None,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn set_rate(&mut self, rate: u32) {
+        let interval = Duration::from_secs(1) / rate;
+        if interval != self.interval {
+            self.interval = interval;
+            self.next = Instant::now();
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        let now = Instant::now();
+
+        if now < self.next {
+            tokio::time::sleep_until(self.next.into()).await;
+        } else if now - self.next > self.interval * MAX_CATCH_UP {
+            let behind = (now - self.next).as_secs_f32() / self.interval.as_secs_f32();
+            self.warn(format!("overrun, {} ticks behind, skipping them", behind as u32));
+            self.next = now;
+        }
+
+        self.next += self.interval;
+    }
+
+    // how long the tick took, anything over the interval makes the server fall behind
+    pub fn finish(&mut self, tick: Tick, elapsed: Duration) {
+        if elapsed > self.interval {
+            self.warn(format!(
+                "slow tick {}, took {:.1}ms of {:.1}ms",
+                tick, elapsed.as_secs_f32() * 1000.0, self.interval.as_secs_f32() * 1000.0,
+            ));
+        }
+    }
+
+    fn warn(&mut self, text: String) {
+        if self.last_warning.is_none_or(|time| time.elapsed() >= WARNING_INTERVAL) {
+            println!("[warning] {}", text);
+            self.last_warning = Some(Instant::now());
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use junglebeast::shared::*;
+use junglebeast::clock::*;
+use junglebeast::interpolation::*;
+
+fn close(a: Option<f64>, b: f64) -> bool {
+    a.is_some_and(|a| (a - b).abs() < 1e-6)
+}
+
+#[test]
+fn first_pong_sets_the_clock() {
+    let mut clock = ServerClock::default();
+    assert_eq!(clock.server_time(10.0), None);
+
+    let Some(ClientMessage::Ping(sent)) = clock.ping(10.0) else { panic!("no ping") };
+    // the pong left the server at 5s and took half of the 0.2s round trip
+    clock.pong(sent, 10.2, 5.0);
+
+    assert!((clock.rtt() - 0.2).abs() < 1e-6);
+    assert!(close(clock.server_time(10.2), 5.1));
+    assert!(close(clock.server_time(11.2), 6.1));
+    assert!(close(clock.prediction_time(10.2), 5.2));
+    assert!(close(clock.interpolation_time(10.2), 5.1 - INTERPOLATION_DELAY.as_secs_f64()));
+}
+
+#[test]
+fn pings_are_rate_limited() {
+    let mut clock = ServerClock::default();
+
+    assert!(clock.ping(0.0).is_some());
+    assert!(clock.ping(PING_INTERVAL / 2.0).is_none());
+    assert!(clock.ping(PING_INTERVAL).is_some());
+}
+
+#[test]
+fn jitter_is_smoothed_and_jumps_are_not() {
+    let mut clock = ServerClock::default();
+    clock.sync(0.0, 100.0);
+
+    // a late answer only moves the estimate a bit
+    clock.sync(1.0, 101.05);
+    let time = clock.server_time(1.0).unwrap();
+    assert!(time > 101.0 && time < 101.05, "{}", time);
+
+    // after a lag spike the new time is taken as it is
+    clock.sync(2.0, 110.0);
+    assert!(close(clock.server_time(2.0), 110.0));
+}
@@ -0,0 +1,118 @@
+// a server without the network: fake players are plain client ids,
+// their messages are handed straight to the game and everything the
+// game sends is kept in events
+
+#![allow(dead_code)]
+
+use junglebeast::shared::*;
+use junglebeast::game::*;
+
+pub const TICK: f32 = 1.0 / 30.0;
+
+pub struct Sim {
+    pub game: Game,
+    pub events: Vec<(Recipient, ServerMessage)>,
+}
+
+impl Sim {
+    // a deathmatch on the test map that starts right away
+    pub async fn new() -> Self {
+        Self::with_cvars(&["sv_warmup=0"]).await
+    }
+
+    pub async fn with_cvars(args: &[&str]) -> Self {
+        let mut cvars = Cvars::default();
+        cvars.apply_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>()).unwrap();
+
+        let mut game = Game::new("dm", TEST_MAP.to_string(), cvars);
+        game.load_map().await.unwrap();
+
+        let mut sim = Self {
+            game,
+            events: Vec::new(),
+        };
+
+        // with no warmup the match starts on the first tick, which respawns everyone
+        sim.step(1).await;
+        sim
+    }
+
+    // client ids start at 1
+    pub fn join(&mut self, count: u64) -> Vec<Entity> {
+        (1..=count).map(|client_id| self.game.join(client_id)).collect()
+    }
+
+    pub fn send(&mut self, client_id: ClientId, msg: ClientMessage) {
+        self.game.handle_msg(client_id, msg);
+    }
+
+    // the command for the next tick, like a client that is right on time
+    pub fn input(&mut self, client_id: ClientId, cmd: UserCmd) {
+        let tick = self.game.current_tick + 1;
+        self.send(client_id, ClientMessage::UserCmds(vec![UserCmd { tick, ..cmd }]));
+    }
+
+    pub async fn step(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.game.tick(TICK).await;
+
+            for (recipient, msgs) in std::mem::take(&mut self.game.outbox) {
+                for msg in msgs {
+                    self.events.push((recipient, msg));
+                }
+            }
+        }
+    }
+
+    pub fn position(&self, id: Entity) -> Vec3 {
+        let handle = *self.game.shared.ecs.get::<&RigidBodyHandle>(id).unwrap();
+        conv_vec_2(*self.game.shared.physics.get_rig(handle).translation())
+    }
+
+    pub fn teleport(&mut self, id: Entity, pos: Vec3) {
+        let handle = *self.game.shared.ecs.get::<&RigidBodyHandle>(id).unwrap();
+        let body = self.game.shared.physics.get_rig_mut(handle);
+        body.set_position(Isometry::translation(pos.x, pos.y, pos.z), true);
+        body.set_linvel(Vector::zeros(), true);
+    }
+
+    pub fn player(&self, id: Entity) -> Player {
+        (*self.game.shared.ecs.get::<&Player>(id).unwrap()).clone()
+    }
+
+    pub fn score(&self, id: Entity) -> Score {
+        (*self.game.shared.ecs.get::<&Score>(id).unwrap()).clone()
+    }
+
+    pub fn kills(&self) -> Vec<&DamageEvent> {
+        self.events.iter()
+            .filter_map(|(_, msg)| match msg {
+                ServerMessage::Kill(event) => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn chat(&self) -> Vec<(&Recipient, &str)> {
+        self.events.iter()
+            .filter_map(|(recipient, msg)| match msg {
+                ServerMessage::Chat { text, .. } => Some((recipient, text.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // shoots at the middle of the body, which is not a headshot
+    pub fn shoot(&mut self, client_id: ClientId, target: Entity, weapon: Weapon) {
+        let point = self.position(target);
+        self.send(client_id, ClientMessage::Shot { target, point, weapon });
+    }
+}
+
+pub fn look(yaw: f32) -> UserCmd {
+    UserCmd { yaw, ..UserCmd::default() }
+}
+
+pub fn walk(yaw: f32) -> UserCmd {
+    UserCmd { forward: 1.0, ..look(yaw) }
+}
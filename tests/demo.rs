@@ -0,0 +1,26 @@
+use junglebeast::shared::*;
+use junglebeast::demo::*;
+
+#[test]
+fn recorded_frames_play_back() {
+    let path = std::env::temp_dir().join("junglebeast-test.demo");
+    let path = path.to_str().unwrap();
+
+    let mut recorder = DemoRecorder::create(path).unwrap();
+    recorder.record(&vec![ServerMessage::Tick(1)]);
+    recorder.record(&vec![ServerMessage::Tick(2), ServerMessage::Chat { from: None, text: "hi".to_string() }]);
+    recorder.flush();
+
+    let mut demo = DemoPlayer::load(path).unwrap();
+    let frames = demo.advance(demo.duration());
+    assert_eq!(frames.len(), 2);
+    assert!(matches!(frames[0].msgs().unwrap()[..], [ServerMessage::Tick(1)]));
+    assert_eq!(frames[1].msgs().unwrap().len(), 2);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_demos_are_an_error() {
+    assert!(DemoPlayer::load("no/such.demo").is_err());
+}
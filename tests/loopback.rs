@@ -0,0 +1,168 @@
+use junglebeast::shared::*;
+use junglebeast::server::*;
+use junglebeast::client::*;
+
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_millis(33);
+
+// a real server and a real client, connected without any ports
+async fn connect() -> (Server, Client) {
+    let args = ServerArgs::parse_from(["server", "127.0.0.1:27960", "--set", "sv_warmup=0"]);
+    let addr = args.addr();
+    let (local, remote) = loopback(addr);
+
+    let mut server = Server::with_socket(args, Box::new(remote));
+    server.init().await.unwrap();
+    let client = Client::with_socket(addr, Box::new(local));
+
+    (server, client)
+}
+
+async fn update(server: &mut Server, client: &mut Client, msgs: ClientMessages) {
+    server.update(TICK).await;
+    client.handle_network(TICK, msgs).await;
+}
+
+async fn wait_for_world(server: &mut Server, client: &mut Client) {
+    for _ in 0..300 {
+        if client.loaded {
+            break;
+        }
+        update(server, client, Vec::new()).await;
+    }
+    assert!(client.loaded, "the world never arrived");
+}
+
+#[tokio::test]
+async fn client_loads_the_world_and_joins() {
+    let (mut server, mut client) = connect().await;
+
+    for _ in 0..300 {
+        if client.loaded {
+            break;
+        }
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+    assert!(client.loaded, "the world never arrived");
+    assert_eq!(client.player, Entity::DANGLING);
+
+    update(&mut server, &mut client, vec![ClientMessage::Join]).await;
+    for _ in 0..30 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+
+    assert_ne!(client.player, Entity::DANGLING);
+    assert!(server.game.client_ids.values().any(|id| *id == client.player));
+    assert_eq!(client.shared.ecs.get::<&Player>(client.player).unwrap().hp(), MAX_HP);
+}
+
+#[tokio::test]
+async fn client_sees_its_player_move() {
+    let (mut server, mut client) = connect().await;
+
+    wait_for_world(&mut server, &mut client).await;
+    update(&mut server, &mut client, vec![ClientMessage::Join]).await;
+    for _ in 0..30 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+
+    let handle = *client.shared.ecs.get::<&RigidBodyHandle>(client.player).unwrap();
+    let start = conv_vec_2(*client.shared.physics.get_rig(handle).translation());
+
+    // every spawn platform is in a corner, the middle is towards -x -z of (15, 15)
+    let yaw = if start.x > 0.0 { std::f32::consts::PI } else { 0.0 };
+    for _ in 0..10 {
+        let cmd = UserCmd { tick: client.server_tick + 1, forward: 1.0, yaw, ..UserCmd::default() };
+        update(&mut server, &mut client, vec![ClientMessage::UserCmds(vec![cmd])]).await;
+    }
+    let cmd = UserCmd { tick: client.server_tick + 1, yaw, ..UserCmd::default() };
+    update(&mut server, &mut client, vec![ClientMessage::UserCmds(vec![cmd])]).await;
+    for _ in 0..10 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+
+    let server_handle = *server.game.shared.ecs.get::<&RigidBodyHandle>(client.player).unwrap();
+    let on_server = conv_vec_2(*server.game.shared.physics.get_rig(server_handle).translation());
+    let on_client = conv_vec_2(*client.shared.physics.get_rig(handle).translation());
+
+    assert!((on_server.x - start.x).abs() > 1.0, "the player did not move from {}", start);
+    assert!(on_client.distance(on_server) < 0.1, "client {} server {}", on_client, on_server);
+}
+
+#[tokio::test]
+async fn batches_are_stamped_with_the_server_tick() {
+    let (mut server, mut client) = connect().await;
+
+    wait_for_world(&mut server, &mut client).await;
+    for _ in 0..10 {
+        update(&mut server, &mut client, Vec::new()).await;
+        assert_eq!(client.server_tick, server.game.current_tick);
+    }
+}
+
+#[tokio::test]
+async fn client_follows_removed_and_reused_bodies() {
+    let (mut server, mut client) = connect().await;
+
+    wait_for_world(&mut server, &mut client).await;
+    update(&mut server, &mut client, vec![ClientMessage::Join]).await;
+    for _ in 0..10 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+    let client_id = *server.game.client_ids.keys().next().unwrap();
+    let old_player = client.player;
+    let old = *server.game.shared.ecs.get::<&RigidBodyHandle>(old_player).unwrap();
+
+    // back as a spectator, then a new player in the freed slot
+    server.game.remove_player(client_id);
+    for _ in 0..10 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+    assert!(!client.shared.physics.state.rigid_body_set.contains(old));
+    assert!(!client.shared.ecs.contains(old_player));
+
+    update(&mut server, &mut client, vec![ClientMessage::Join]).await;
+    for _ in 0..10 {
+        update(&mut server, &mut client, Vec::new()).await;
+    }
+
+    let handle = *server.game.shared.ecs.get::<&RigidBodyHandle>(client.player).unwrap();
+    assert_eq!(handle.0.into_raw_parts().0, old.0.into_raw_parts().0);
+    assert!(client.shared.physics.state.rigid_body_set.contains(handle));
+    assert_eq!(client.shared.physics.state.rigid_body_set.len(), server.game.shared.physics.state.rigid_body_set.len());
+}
+
+#[tokio::test]
+async fn client_joins_over_a_bad_network() {
+    let args = ServerArgs::parse_from(["server", "127.0.0.1:27960", "--set", "sv_warmup=0"]);
+    let addr = args.addr();
+    let (local, remote) = loopback(addr);
+    let conditions = NetConditions { latency: 10, jitter: 10, loss: 0.1, duplicate: 0.1 };
+
+    let mut server = Server::with_socket(args, Box::new(SimSocket::new(remote, conditions.clone())));
+    server.init().await.unwrap();
+    let mut client = Client::with_socket(addr, Box::new(SimSocket::new(local, conditions)));
+
+    // the latency is real time, so the ticks have to take some
+    for _ in 0..300 {
+        if client.loaded {
+            break;
+        }
+        update(&mut server, &mut client, Vec::new()).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(client.loaded, "the world never arrived");
+
+    // the join is reliable, it gets through the loss eventually
+    update(&mut server, &mut client, vec![ClientMessage::Join]).await;
+    for _ in 0..300 {
+        if client.player != Entity::DANGLING {
+            break;
+        }
+        update(&mut server, &mut client, Vec::new()).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_ne!(client.player, Entity::DANGLING);
+    assert!(server.game.client_ids.values().any(|id| *id == client.player));
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::*;
+use junglebeast::shared::*;
+
+#[test]
+fn every_model_loads() {
+    for model in ModelId::all() {
+        let parts = Shared::load_model(&model.path()).unwrap();
+        assert!(!parts.is_empty(), "{:?} has no meshes", model);
+    }
+}
+
+#[test]
+fn the_player_model_fits_the_player_box() {
+    for (mesh, transform) in Shared::load_model(&ModelId::Player.path()).unwrap() {
+        let matrix = transform.matrix();
+        for vertex in &mesh.vertices {
+            let pos = matrix.transform_point3(vertex.position);
+            assert!(pos.abs().cmple(PLAYER_SIZE / 2.0 + 0.05).all(), "{} is outside of the box", pos);
+        }
+    }
+}
+
+#[test]
+fn nested_nodes_get_the_transforms_of_their_parents() {
+    let dir = std::env::temp_dir().join("junglebeast-nested-model");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // one triangle, 3 u16 indices padded to 8 bytes then 3 positions
+    let mut buffer: Vec<u8> = [0u16, 1, 2, 0].iter().flat_map(|i| i.to_le_bytes()).collect();
+    buffer.extend([0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter().flat_map(|f| f.to_le_bytes()));
+    std::fs::write(dir.join("nested.bin"), &buffer).unwrap();
+
+    let gltf = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "translation": [0, 2, 0], "scale": [2, 2, 2], "children": [1] },
+            { "translation": [1, 0, 0], "mesh": 0 }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 1 }, "indices": 0 }] }],
+        "buffers": [{ "uri": "nested.bin", "byteLength": 44 }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 6 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 36 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }
+        ]
+    }"#;
+    let path = dir.join("nested.gltf");
+    std::fs::write(&path, gltf).unwrap();
+
+    let parts = Shared::load_model(path.to_str().unwrap()).unwrap();
+    assert_eq!(parts.len(), 1);
+    let (_, transform) = &parts[0];
+    assert!(transform.pos.abs_diff_eq(vec3(2.0, 2.0, 0.0), 1e-5), "{}", transform.pos);
+    assert!(transform.scale.abs_diff_eq(Vec3::splat(2.0), 1e-5), "{}", transform.scale);
+}
+
+#[tokio::test]
+async fn dying_spawns_gibs_with_gib_models() {
+    let mut sim = Sim::new().await;
+    let ids = sim.join(2);
+    assert_eq!(*sim.game.shared.ecs.get::<&ModelId>(ids[0]).unwrap(), ModelId::Player);
+
+    for _ in 0..5 {
+        sim.shoot(1, ids[1], Weapon::Gun);
+    }
+    assert!(sim.player(ids[1]).dead());
+
+    let gibs: Vec<ModelId> = sim.game.shared.ecs.query::<&ModelId>().iter()
+        .map(|(_, model)| *model)
+        .filter(|model| matches!(model, ModelId::Gib(_)))
+        .collect();
+    assert_eq!(gibs.len(), 5);
+    assert!(gibs.iter().all(|model| matches!(model, ModelId::Gib(index) if *index < GIB_MODELS)));
+}
@@ -0,0 +1,94 @@
+use junglebeast::shared::*;
+use junglebeast::movement::*;
+
+const DT: f32 = 1.0 / 60.0;
+
+fn run(mut vel: Vec3, mode: MoveMode, steps: usize, mut cmd: impl FnMut(usize) -> UserCmd) -> Vec3 {
+    let cvars = Cvars::default();
+    for step in 0..steps {
+        vel = player_velocity(vel, &cmd(step), mode, false, &cvars, DT);
+        // the gravity of the physics
+        if mode == MoveMode::Swim {
+            vel.y -= cvars.sv_gravity * DT;
+        }
+    }
+    vel
+}
+
+#[test]
+fn walking_speeds_up_to_the_move_speed() {
+    let speed = Cvars::default().sv_move_speed;
+    let forward = UserCmd { forward: 1.0, side: 1.0, ..UserCmd::default() };
+
+    let vel = run(Vec3::ZERO, MoveMode::Ground, 6, |_| forward.clone());
+    assert!(vel.length() > speed * 0.5 && vel.length() < speed, "{}", vel);
+
+    let vel = run(Vec3::ZERO, MoveMode::Ground, 120, |_| forward.clone());
+    assert!((vel.length() - speed).abs() < 0.01, "{}", vel);
+}
+
+#[test]
+fn friction_stops_a_player() {
+    let vel = run(vec3(6.0, 0.0, 0.0), MoveMode::Ground, 30, |_| UserCmd::default());
+    assert_eq!(vel, Vec3::ZERO);
+
+    // no friction in the air
+    let vel = run(vec3(6.0, 0.0, 0.0), MoveMode::Air, 30, |_| UserCmd::default());
+    assert_eq!(vel, vec3(6.0, 0.0, 0.0));
+}
+
+#[test]
+fn air_control_is_capped() {
+    let speed = Cvars::default().sv_air_speed;
+    let vel = run(Vec3::ZERO, MoveMode::Air, 120, |_| UserCmd { forward: 1.0, ..UserCmd::default() });
+    assert!((vel.length() - speed).abs() < 0.01, "{}", vel);
+}
+
+#[test]
+fn air_strafing_gains_speed() {
+    let start = Cvars::default().sv_move_speed;
+
+    // strafe right while turning right, so the velocity is always a bit behind the view
+    let vel = run(vec3(start, 0.0, 0.0), MoveMode::Air, 120, |step| UserCmd {
+        side: 1.0,
+        yaw: step as f32 * 0.03,
+        ..UserCmd::default()
+    });
+    assert!(vel.length() > start * 1.2, "{} is not faster than {}", vel.length(), start);
+}
+
+#[test]
+fn crouching_walks_slower() {
+    let cvars = Cvars::default();
+    let forward = UserCmd { forward: 1.0, ..UserCmd::default() };
+
+    let mut vel = Vec3::ZERO;
+    for _ in 0..120 {
+        vel = player_velocity(vel, &forward, MoveMode::Ground, true, &cvars, DT);
+    }
+    assert!((vel.length() - cvars.sv_crouch_speed).abs() < 0.01, "{}", vel);
+}
+
+#[test]
+fn swimming_follows_the_pitch() {
+    let speed = Cvars::default().sv_swim_speed;
+
+    // looking up and swimming forward goes up, not just forward
+    let vel = run(Vec3::ZERO, MoveMode::Swim, 120, |_| UserCmd { forward: 1.0, pitch: 1.0, ..UserCmd::default() });
+    assert!(vel.y > speed * 0.5, "{}", vel);
+
+    let mut dive = UserCmd::default();
+    dive.press(BUTTON_CROUCH);
+    let vel = run(Vec3::ZERO, MoveMode::Swim, 120, |_| dive.clone());
+    assert!(vel.y < -speed * 0.5, "{}", vel);
+}
+
+#[test]
+fn water_slows_down_sinking() {
+    // way slower than falling for the same time
+    let vel = run(Vec3::ZERO, MoveMode::Swim, 120, |_| UserCmd::default());
+    assert!(vel.y < 0.0 && vel.y > -1.0, "{}", vel);
+
+    let vel = run(vec3(10.0, 0.0, 0.0), MoveMode::Swim, 120, |_| UserCmd::default());
+    assert!(vel.x < 1.0, "{}", vel);
+}
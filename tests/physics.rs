@@ -0,0 +1,42 @@
+use junglebeast::shared::*;
+
+// index and generation
+type Handles = Vec<(u32, u32)>;
+
+fn handles(physics: &Physics) -> (Handles, Handles) {
+    let mut bodies: Vec<_> = physics.state.rigid_body_set.iter().map(|(handle, _)| handle.0.into_raw_parts()).collect();
+    let mut colliders: Vec<_> = physics.state.collider_set.iter().map(|(handle, _)| handle.0.into_raw_parts()).collect();
+    bodies.sort();
+    colliders.sort();
+    (bodies, colliders)
+}
+
+#[test]
+fn mirrored_sets_keep_the_handles_of_removed_and_reused_slots() {
+    let mut server = Physics::new();
+    let mut cubes = Vec::new();
+    for i in 0..10 {
+        cubes.push(server.spawn_cube(vec3(i as f32, 0.0, 0.0), Vec3::ONE).0);
+    }
+    server.spawn_sensor(Vec3::ZERO, 1.0);
+
+    // slots freed in a mixed order, then reused and grown past the end
+    for i in [3, 7, 4] {
+        server.remove_body(cubes[i]);
+    }
+    for _ in 0..5 {
+        server.spawn_cube(Vec3::ZERO, Vec3::ONE);
+    }
+    server.remove_body(cubes[0]);
+    server.spawn_cube(Vec3::ZERO, Vec3::ONE);
+
+    let mut client = Physics::new();
+    assert!(client.state.mirror(server.get_physics_diff()));
+    assert_eq!(handles(&client), handles(&server));
+
+    // the colliders still follow their bodies
+    for (handle, body) in server.state.rigid_body_set.iter() {
+        assert_eq!(client.get_rig(handle).colliders(), body.colliders());
+    }
+    client.step(1.0 / 60.0, 9.81);
+}
@@ -0,0 +1,437 @@
+mod common;
+
+use common::*;
+use junglebeast::shared::*;
+use junglebeast::game::*;
+
+// the spawn platforms of the test map
+const PLATFORM: Vec3 = vec3(15.0, 1.9, 15.0);
+const OTHER_PLATFORM: Vec3 = vec3(-15.0, 1.9, -15.0);
+
+#[tokio::test]
+async fn players_land_on_spawn_platforms() {
+    let mut sim = Sim::new().await;
+    let players = sim.join(2);
+    sim.step(60).await;
+
+    for id in players {
+        let pos = sim.position(id);
+        assert!(pos.x.abs() > 10.0 && pos.z.abs() > 10.0, "{} is not on a platform", pos);
+        assert!((pos.y - PLATFORM.y).abs() < 0.5, "{} is not standing", pos);
+        assert_eq!(sim.player(id).hp(), MAX_HP);
+    }
+}
+
+#[tokio::test]
+async fn walking_follows_the_yaw() {
+    let mut sim = Sim::new().await;
+    let [id] = sim.join(1)[..] else { unreachable!() };
+    sim.teleport(id, PLATFORM);
+    sim.step(15).await;
+    let start = sim.position(id);
+
+    // facing -x, so back towards the middle of the platform
+    sim.input(1, walk(std::f32::consts::PI));
+    sim.step(9).await;
+    sim.input(1, look(std::f32::consts::PI));
+    sim.step(3).await;
+
+    let moved = sim.position(id) - start;
+    let expected = sim.game.shared.cvars.sv_move_speed * TICK * 9.0;
+    assert!(moved.x < 0.0 && (moved.x.abs() - expected).abs() < 0.5, "moved {}, expected {}", moved, expected);
+    assert!(moved.z.abs() < 0.1 && moved.y.abs() < 0.1, "moved {}", moved);
+}
+
+#[tokio::test]
+async fn gun_kills_in_five_body_shots() {
+    let mut sim = Sim::new().await;
+    let [attacker, victim] = sim.join(2)[..] else { unreachable!() };
+    sim.teleport(attacker, PLATFORM);
+    sim.teleport(victim, OTHER_PLATFORM);
+    sim.step(1).await;
+
+    for shot in 1..=5 {
+        sim.shoot(1, victim, Weapon::Gun);
+        sim.step(1).await;
+        assert_eq!(sim.player(victim).hp(), MAX_HP.saturating_sub(20 * shot));
+    }
+
+    assert!(sim.player(victim).dead());
+    assert_eq!(sim.player(attacker).ammo(Weapon::Gun), 45);
+    assert_eq!(sim.score(attacker).frags, 1);
+    assert_eq!(sim.score(victim).deaths, 1);
+
+    let kills = sim.kills();
+    assert_eq!(kills.len(), 1);
+    assert_eq!((kills[0].attacker, kills[0].victim), (attacker, victim));
+    let announcement = format!("{} fragged {}", player_name(attacker), player_name(victim));
+    assert!(sim.chat().contains(&(&Recipient::All, announcement.as_str())));
+}
+
+#[tokio::test]
+async fn shots_that_miss_the_target_do_no_damage() {
+    let mut sim = Sim::new().await;
+    let [attacker, victim] = sim.join(2)[..] else { unreachable!() };
+    sim.teleport(attacker, PLATFORM);
+    sim.teleport(victim, OTHER_PLATFORM);
+    sim.step(1).await;
+
+    let point = sim.position(victim) + vec3(0.0, 5.0, 0.0);
+    sim.send(1, ClientMessage::Shot { target: victim, point, weapon: Weapon::Gun });
+    sim.send(1, ClientMessage::Shot { target: attacker, point: sim.position(attacker), weapon: Weapon::Gun });
+    sim.step(1).await;
+
+    assert_eq!(sim.player(victim).hp(), MAX_HP);
+    assert_eq!(sim.player(attacker).hp(), MAX_HP);
+}
+
+#[tokio::test]
+async fn respawn_waits_for_the_delay() {
+    let mut sim = Sim::new().await;
+    let [_, victim] = sim.join(2)[..] else { unreachable!() };
+    sim.step(1).await;
+
+    for _ in 0..5 {
+        sim.shoot(1, victim, Weapon::Gun);
+    }
+    sim.step(1).await;
+    assert!(sim.player(victim).dead());
+
+    sim.send(2, ClientMessage::Respawn);
+    sim.step(1).await;
+    assert!(sim.player(victim).dead());
+
+    sim.step(60).await;
+    sim.send(2, ClientMessage::Respawn);
+    sim.step(1).await;
+    assert_eq!(sim.player(victim).hp(), MAX_HP);
+}
+
+#[tokio::test]
+async fn damage_comes_from_the_cvars() {
+    let mut sim = Sim::with_cvars(&["sv_warmup=0", "sv_gun_damage=35"]).await;
+    let [_, victim] = sim.join(2)[..] else { unreachable!() };
+    sim.step(1).await;
+
+    sim.shoot(1, victim, Weapon::Gun);
+    sim.step(1).await;
+    assert_eq!(sim.player(victim).hp(), MAX_HP - 35);
+}
+
+#[tokio::test]
+async fn team_chat_without_teams_goes_back_to_the_sender() {
+    let mut sim = Sim::new().await;
+    sim.join(2);
+    sim.step(1).await;
+    sim.events.clear();
+
+    sim.send(1, ClientMessage::Chat { text: "hi all".to_string(), team_only: false });
+    sim.send(2, ClientMessage::Chat { text: "hi team".to_string(), team_only: true });
+    sim.step(1).await;
+
+    assert_eq!(sim.chat(), vec![(&Recipient::All, "hi all"), (&Recipient::Client(2), "hi team")]);
+}
+
+#[tokio::test]
+async fn spectators_chat_with_everyone_or_other_spectators() {
+    let mut sim = Sim::new().await;
+    sim.join(1);
+    sim.step(1).await;
+    sim.events.clear();
+
+    // client 7 never joined
+    sim.send(7, ClientMessage::Chat { text: "hi all".to_string(), team_only: false });
+    sim.send(7, ClientMessage::Chat { text: "hi spectators".to_string(), team_only: true });
+    sim.step(1).await;
+
+    assert_eq!(sim.chat(), vec![
+        (&Recipient::All, "spectator 7: hi all"),
+        (&Recipient::Spectators, "spectator 7: hi spectators"),
+    ]);
+}
+
+#[tokio::test]
+async fn the_same_inputs_give_the_same_positions() {
+    let mut positions = Vec::new();
+
+    for _ in 0..2 {
+        let mut sim = Sim::new().await;
+        let [a, b] = sim.join(2)[..] else { unreachable!() };
+        sim.teleport(a, PLATFORM);
+        sim.teleport(b, OTHER_PLATFORM);
+
+        for tick in 0..60 {
+            let forward = if tick % 20 < 10 { 1.0 } else { 0.0 };
+            let side = if tick % 7 < 3 { 0.5 } else { 0.0 };
+            sim.input(1, UserCmd { forward, side, ..look(2.0) });
+            sim.input(2, UserCmd { forward, side, ..look(-1.0) });
+            sim.step(1).await;
+        }
+
+        positions.push((sim.position(a), sim.position(b)));
+    }
+
+    assert_eq!(positions[0], positions[1]);
+}
+
+#[tokio::test]
+async fn holding_jump_jumps_once() {
+    let mut sim = Sim::new().await;
+    let [id] = sim.join(1)[..] else { unreachable!() };
+    sim.teleport(id, PLATFORM);
+    sim.step(90).await;
+    let ground = sim.position(id).y;
+
+    let mut jump = look(0.0);
+    jump.press(BUTTON_JUMP);
+    sim.input(1, jump.clone());
+
+    // up and down again, then still on the ground while the button is held
+    let mut highest = ground;
+    for _ in 0..150 {
+        sim.input(1, jump.clone());
+        sim.step(1).await;
+        highest = highest.max(sim.position(id).y);
+    }
+    assert!(highest > ground + 0.5, "never jumped, highest {}", highest);
+    assert!((sim.position(id).y - ground).abs() < 0.05, "{} is still in the air", sim.position(id));
+
+    sim.input(1, look(0.0));
+    sim.step(1).await;
+    sim.input(1, jump);
+    sim.step(5).await;
+    assert!(sim.position(id).y > ground + 0.3, "pressing again did not jump");
+}
+
+async fn standing_player(sim: &mut Sim) -> (Entity, f32) {
+    let [id] = sim.join(1)[..] else { unreachable!() };
+    sim.teleport(id, PLATFORM);
+    sim.step(60).await;
+    (id, sim.position(id).y)
+}
+
+fn jumping() -> UserCmd {
+    let mut cmd = look(0.0);
+    cmd.press(BUTTON_JUMP);
+    cmd
+}
+
+#[tokio::test]
+async fn a_jump_pressed_just_before_landing_is_not_lost() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+
+    sim.input(1, jumping());
+    sim.step(1).await;
+    sim.input(1, look(0.0));
+
+    // press again while still falling, a tick before the ground
+    let mut last = sim.position(id).y;
+    let mut landing = false;
+    for _ in 0..90 {
+        sim.step(1).await;
+        let y = sim.position(id).y;
+        if y < last && y < ground + 0.2 {
+            landing = true;
+            break;
+        }
+        last = y;
+    }
+    assert!(landing, "the first jump never came down at {}", sim.position(id));
+    sim.input(1, jumping());
+    sim.step(1).await;
+    sim.input(1, look(0.0));
+    sim.step(5).await;
+
+    assert!(sim.position(id).y > ground + 0.3, "the second jump was lost at {}", sim.position(id));
+}
+
+#[tokio::test]
+async fn auto_hop_keeps_jumping() {
+    let mut sim = Sim::with_cvars(&["sv_warmup=0", "sv_auto_hop=true"]).await;
+    let (id, ground) = standing_player(&mut sim).await;
+
+    // up, down and up again while holding the button
+    let mut landed = false;
+    let mut jumped_again = false;
+    for _ in 0..90 {
+        sim.input(1, jumping());
+        sim.step(1).await;
+        let y = sim.position(id).y;
+        if y < ground + 0.1 {
+            landed = true;
+        } else if landed && y > ground + 0.5 {
+            jumped_again = true;
+        }
+    }
+    assert!(jumped_again);
+}
+
+#[tokio::test]
+async fn small_ledges_are_walked_up() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+    let feet = ground - PLAYER_SIZE.y / 2.0;
+
+    // a low ledge towards the middle of the platform, with a wall behind it
+    for (x, width, height) in [(13.0, 2.0, 0.3), (11.5, 1.0, 2.0)] {
+        let collider = ColliderBuilder::cuboid(width / 2.0, height / 2.0, 2.0)
+            .translation(vector![x, feet + height / 2.0, PLATFORM.z])
+            .build();
+        sim.game.shared.physics.state.collider_set.insert(collider);
+    }
+    sim.step(1).await;
+
+    sim.input(1, walk(std::f32::consts::PI));
+    sim.step(30).await;
+
+    let pos = sim.position(id);
+    assert!((pos.y - (ground + 0.3)).abs() < 0.1, "{} is not on the ledge", pos);
+    assert!(pos.x > 12.0, "{} went through the wall", pos);
+}
+
+fn crouching() -> UserCmd {
+    let mut cmd = look(0.0);
+    cmd.press(BUTTON_CROUCH);
+    cmd
+}
+
+fn box_height(sim: &Sim, id: Entity) -> f32 {
+    let handle = *sim.game.shared.ecs.get::<&RigidBodyHandle>(id).unwrap();
+    let physics = &sim.game.shared.physics;
+    let col = physics.get_rig(handle).colliders()[0];
+    physics.get_col(col).shape().as_cuboid().unwrap().half_extents.y * 2.0
+}
+
+#[tokio::test]
+async fn crouching_under_a_low_ceiling_stays_crouched() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+    let feet = ground - PLAYER_SIZE.y / 2.0;
+
+    sim.input(1, crouching());
+    sim.step(10).await;
+    assert!(sim.player(id).crouched);
+    assert_eq!(box_height(&sim, id), CROUCH_SIZE.y);
+    let crouched = sim.position(id).y;
+    assert!((crouched - (feet + CROUCH_SIZE.y / 2.0)).abs() < 0.05, "{} is not on the floor", crouched);
+
+    // a ceiling too low to stand under
+    let collider = ColliderBuilder::cuboid(1.0, 0.25, 2.0)
+        .translation(vector![PLATFORM.x, feet + 1.5 + 0.25, PLATFORM.z])
+        .build();
+    sim.game.shared.physics.state.collider_set.insert(collider);
+    sim.step(1).await;
+
+    sim.input(1, look(0.0));
+    sim.step(10).await;
+    assert!(sim.player(id).crouched, "stood up into the ceiling");
+    assert!((sim.position(id).y - crouched).abs() < 0.05);
+
+    // out from under it, there is room again
+    sim.input(1, walk(std::f32::consts::PI));
+    sim.step(30).await;
+    assert!(!sim.player(id).crouched);
+    assert_eq!(box_height(&sim, id), PLAYER_SIZE.y);
+    assert!((sim.position(id).y - ground).abs() < 0.1, "{}", sim.position(id));
+}
+
+#[tokio::test]
+async fn players_swim_up_and_float_in_water() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+    let feet = ground - PLAYER_SIZE.y / 2.0;
+
+    let surface = feet + 6.0;
+    sim.game.shared.ecs.spawn((Water { min: PLATFORM - vec3(5.0, 3.0, 5.0), max: vec3(PLATFORM.x + 5.0, surface, PLATFORM.z + 5.0) },));
+
+    // no jumping out of the water, holding the button swims up to the surface
+    let mut highest = ground;
+    for _ in 0..90 {
+        sim.input(1, jumping());
+        sim.step(1).await;
+        highest = highest.max(sim.position(id).y);
+    }
+    assert!(sim.player(id).swimming || sim.position(id).y > surface);
+    assert!(highest > surface - 0.5, "only got up to {}", highest);
+    assert!(highest < surface + 2.0, "jumped out to {}", highest);
+
+    // and sinks slowly without input
+    sim.input(1, look(0.0));
+    sim.step(15).await;
+    let y = sim.position(id).y;
+    assert!(y > surface - 2.0, "sank to {}", y);
+}
+
+// a box mover on top of the spawn platform, its top 0.5 above the floor
+fn spawn_platform_mover(sim: &mut Sim, feet: f32, props: MoverProps) -> Entity {
+    let transform = Transform::new(vec3(PLATFORM.x, feet + 0.25, PLATFORM.z));
+    let collider = ColliderBuilder::cuboid(1.5, 0.25, 1.5).build();
+    let (handle, mover) = sim.game.shared.spawn_mover(&transform, collider, &props);
+    sim.game.shared.ecs.spawn((handle, mover, transform))
+}
+
+#[tokio::test]
+async fn movers_carry_the_players_standing_on_them() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+    let feet = ground - PLAYER_SIZE.y / 2.0;
+
+    let props = MoverProps { path: vec![vec3(-3.0, 0.0, 0.0)], speed: 1.0, wait: 0.0, trigger: None };
+    let platform = spawn_platform_mover(&mut sim, feet, props);
+    sim.teleport(id, vec3(PLATFORM.x, ground + 1.0, PLATFORM.z));
+    sim.step(30).await;
+    let start = sim.position(id) - sim.position(platform);
+    assert!((start.y - (PLAYER_SIZE.y / 2.0 + 0.25)).abs() < 0.1, "{} is not on the mover", start);
+
+    // still on the same spot of it, a second later
+    sim.step(30).await;
+    let pos = sim.position(id) - sim.position(platform);
+    assert!(sim.position(platform).x < PLATFORM.x - 1.5);
+    assert!(pos.distance(start) < 0.05, "moved from {} to {} on the mover", start, pos);
+
+    // and its mesh goes along
+    let transform = *sim.game.shared.ecs.get::<&Transform>(platform).unwrap();
+    assert_eq!(transform.pos, sim.position(platform));
+}
+
+#[tokio::test]
+async fn triggered_movers_wait_for_a_player() {
+    let mut sim = Sim::new().await;
+    let (id, ground) = standing_player(&mut sim).await;
+    let feet = ground - PLAYER_SIZE.y / 2.0;
+
+    // a door that slides up when someone comes close
+    sim.teleport(id, OTHER_PLATFORM);
+    let props = MoverProps { path: vec![vec3(0.0, 3.0, 0.0)], speed: 3.0, wait: 1.0, trigger: Some(3.0) };
+    let door = spawn_platform_mover(&mut sim, feet, props);
+    let closed = sim.position(door);
+
+    sim.step(30).await;
+    assert_eq!(sim.position(door), closed);
+
+    sim.teleport(id, vec3(PLATFORM.x + 2.5, ground, PLATFORM.z));
+    sim.step(45).await;
+    assert!(sim.position(door).y > closed.y + 2.9, "the door is at {}", sim.position(door));
+
+    // closes again once nobody is there
+    sim.teleport(id, OTHER_PLATFORM);
+    sim.step(90).await;
+    assert!((sim.position(door).y - closed.y).abs() < 0.01, "the door is at {}", sim.position(door));
+}
+
+#[tokio::test]
+async fn a_broken_map_leaves_the_game_running() {
+    let mut sim = Sim::new().await;
+    let [id] = sim.join(1)[..] else { unreachable!() };
+    sim.step(5).await;
+
+    // a file, but not a gltf
+    let err = sim.game.change_map("Cargo.toml".to_string()).await.unwrap_err();
+    assert!(err.contains("Cargo.toml"), "{}", err);
+    assert_eq!(sim.game.map, TEST_MAP);
+    assert_eq!(sim.game.client_ids.get(&1), Some(&id));
+
+    sim.step(5).await;
+    assert_eq!(sim.player(id).hp(), MAX_HP);
+}
@@ -0,0 +1,35 @@
+use junglebeast::shared::*;
+
+#[test]
+fn chunks_are_put_back_together_in_any_order() {
+    let payload: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| i as u8).collect();
+    let mut sender = TransferSender::new();
+    sender.send(payload.clone());
+
+    let mut chunks: Vec<Chunk> = std::iter::from_fn(|| sender.next_chunk()).collect();
+    assert_eq!(chunks.len(), 3);
+    chunks.reverse();
+
+    let mut receiver = TransferReceiver::new();
+    assert!(receiver.receive(chunks[0].clone()).is_none());
+    assert!(receiver.receive(chunks[1].clone()).is_none());
+    assert_eq!(receiver.receive(chunks[2].clone()), Some(payload));
+    assert!(receiver.progress().is_none());
+}
+
+#[test]
+fn bad_chunks_drop_the_transfer() {
+    let chunk = |index, count, total_len| Chunk { transfer: 1, index, count, total_len, data: vec![0; 10] };
+    let mut receiver = TransferReceiver::new();
+
+    for bad in [chunk(0, 0, 10), chunk(1, 1, 10), chunk(0, u32::MAX, u64::MAX), chunk(0, 5, 10)] {
+        assert!(receiver.receive(bad).is_none());
+        assert!(receiver.progress().is_none());
+    }
+
+    // a started transfer goes away too
+    assert!(receiver.receive(chunk(0, 2, CHUNK_SIZE as u64 + 10)).is_none());
+    assert!(receiver.progress().is_some());
+    assert!(receiver.receive(chunk(5, 2, CHUNK_SIZE as u64 + 10)).is_none());
+    assert!(receiver.progress().is_none());
+}
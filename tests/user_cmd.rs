@@ -0,0 +1,52 @@
+use junglebeast::shared::*;
+
+fn cmd(tick: Tick) -> UserCmd {
+    UserCmd { tick, forward: 1.0, ..UserCmd::default() }
+}
+
+#[test]
+fn redundant_copies_are_dropped() {
+    let mut buffer = CmdBuffer::default();
+
+    // every packet has the last commands again
+    for cmds in [vec![cmd(1)], vec![cmd(1), cmd(2)], vec![cmd(1), cmd(2), cmd(3)]] {
+        for cmd in cmds {
+            buffer.push(cmd, 0);
+        }
+    }
+
+    for tick in 1..=3 {
+        assert_eq!(buffer.take(tick).map(|cmd| cmd.tick), Some(tick));
+    }
+    assert_eq!(buffer.take(4), None);
+}
+
+#[test]
+fn early_commands_wait_and_late_ones_are_merged() {
+    let mut buffer = CmdBuffer::default();
+
+    buffer.push(cmd(10), 0);
+    assert_eq!(buffer.take(9), None);
+    assert_eq!(buffer.take(10).map(|cmd| cmd.tick), Some(10));
+
+    // both arrived after their tick, the jump of the older one is kept
+    let mut jump = cmd(11);
+    jump.press(BUTTON_JUMP);
+    buffer.push(jump, 12);
+    buffer.push(cmd(12), 12);
+
+    let taken = buffer.take(13).unwrap();
+    assert_eq!(taken.tick, 12);
+    assert!(taken.pressed(BUTTON_JUMP));
+}
+
+#[test]
+fn bad_values_are_cleaned_up() {
+    let mut buffer = CmdBuffer::default();
+    buffer.push(UserCmd { tick: 1000, forward: 5.0, side: f32::NAN, pitch: 3.0, ..UserCmd::default() }, 0);
+
+    // too far ahead, so it is for the next possible tick instead
+    let taken = buffer.take(100).unwrap();
+    assert!(taken.tick < 100);
+    assert_eq!((taken.forward, taken.side, taken.pitch), (1.0, 0.0, 1.5));
+}